@@ -5,7 +5,8 @@
 //No usamos la biblioteca estandar de Rust en gear porque sus bibliotecas la reescriben
 use gear_lib::multitoken::io::*;
 use gmeta::{In, InOut, Metadata};
-use gstd::{prelude::*, ActorId};
+use gstd::{prelude::*, ActorId, MessageId};
+use sp_core_hashing::blake2_256;
 
 pub struct ContractMetadata;
 
@@ -24,8 +25,10 @@ impl Metadata for ContractMetadata {
 pub struct State {
     //La direccion del dueño del smart contract
     pub owner_id: ActorId,
+    //El tipo de programa donde se crean los boletos
+    pub backend: TokenBackend,
     //La direccion del smart contract donde se realizan las operaciones con los tokens
-    //este es un contrato GMT-1155 que se puede encontrar en github.com/gear-dapps/multitoken
+    //por defecto es un contrato GMT-1155 que se puede encontrar en github.com/gear-dapps/multitoken
     pub contract_id: ActorId,
     //Nombre del evento
     pub name: String,
@@ -51,11 +54,122 @@ pub struct State {
     pub running: bool,
     /// El vector con la metadata que le corresponde a cada comprador
     pub metadata: Vec<(ActorId, Tickets)>,
+    //Las operaciones con el programa de tokens que no se han completado, en el orden en que se hacen
+    pub pending_operations: Vec<PendingOperation>,
+    //El numero de operaciones con el programa de tokens que se han registrado, el id de cada una sale de aqui
+    //El id de la operacion tambien va en los ids de transaccion del programa de NFTs
+    pub operation_counter: u64,
+    //Los ids de compra que manda cada comprador, se usan para no repetir compras reenviadas
+    pub purchase_requests: Vec<(ActorId, Vec<(u128, PurchaseReceipt)>)>,
+    //La razon por la que el dueño pauso el smart contract, si esta pausado
+    pub pause_reason: Option<String>,
+    //La direccion propuesta como nuevo dueño que todavia no acepta
+    pub pending_owner: Option<ActorId>,
+    //Los saldos de boletos fungibles de cada comprador en el registro interno
+    pub ledger: Vec<(ActorId, u128)>,
+    //El dueño de cada boleto del registro interno despues de volverlos NFTs
+    pub ledger_nfts: Vec<(u128, ActorId)>,
+    //Las secciones del lugar con sus filas y asientos, esta vacio si los boletos no tienen asiento asignado
+    pub seat_map: Vec<SeatSection>,
+    //Los asientos vendidos con el id del boleto que los ocupa
+    pub sold_seats: Vec<(Seat, u128)>,
+    //Los boletos apartados por cada comprador que todavia no se pagan
+    pub reservations: Vec<(ActorId, Reservation)>,
+    //Hasta cuando cada cuenta no puede volver a apartar boletos, asi no los aparta uno tras otro sin pagarlos
+    pub reservation_cooldowns: Vec<(ActorId, u64)>,
+    //La lista de espera en orden con los boletos que quiere cada cuenta
+    pub waitlist: Vec<(ActorId, u128)>,
+    //El sorteo de boletos del concierto, si es que hay uno
+    pub lottery: Option<Lottery>,
+    //El modo de venta con el que se calcula el precio de los boletos
+    pub sale_mode: SaleMode,
+    //El valor cobrado por la venta de boletos que guarda el smart contract
+    pub proceeds: u128,
+    //El precio de cierre de la subasta con precio uniforme, cuando se vende el ultimo boleto
+    pub clearing_price: Option<u128>,
+    //Lo que pago cada comprador en la subasta con precio uniforme
+    pub auction_payments: Vec<(ActorId, AuctionPayment)>,
+    //La subasta de ofertas selladas de boletos premium, si es que hay una
+    pub sealed_auction: Option<SealedAuction>,
+    //Los boletos ganados en la subasta de ofertas selladas que faltan de reclamar
+    pub auction_wins: Vec<(ActorId, AuctionWin)>,
+    //Los codigos de descuento registrados por el creador, guardados por su hash
+    pub promo_codes: Vec<([u8; 32], PromoCode)>,
+    //La fase de precio activa cuando se leyo el estado
+    pub price_phase: Option<PricePhase>,
+    //Los beneficiarios de lo recaudado con su porcentaje
+    pub revenue_split: Vec<(ActorId, u8)>,
+    //Lo que ya retiro cada beneficiario
+    pub withdrawals: Vec<(ActorId, u128)>,
+    //Lo recaudado en conciertos anteriores que cada beneficiario todavia no retira
+    pub carried_revenue: Vec<(ActorId, u128)>,
+    //La comision de la plataforma que se cobra en cada venta, si es que hay una
+    pub platform_fee: Option<PlatformFee>,
+    //Las comisiones que el dueño todavia no retira
+    pub fee_balance: u128,
+    //Todas las comisiones cobradas
+    pub total_fees: u128,
+    //Lo que pago cada comprador, se guarda hasta que se libera al organizador o se reembolsa
+    pub escrow: Vec<(ActorId, EscrowPayment)>,
+    //El tiempo despues del hold en el que todavia se puede disputar el concierto
+    pub dispute_period: u64,
+    //El block timestamp en el que se hizo el hold del concierto
+    pub held_at: Option<u64>,
+    pub cancelled: bool,
+    //Los boletos de cortesia que puede dar el creador, aparte de los boletos a la venta
+    pub complimentary_allocation: u128,
+    pub complimentary_left: u128,
+    //Los ids de los boletos que se dieron como cortesia
+    pub complimentary_tickets: Vec<u128>,
+    //La cuota de boletos de cada agencia socia con lo que lleva vendido
+    pub partner_quotas: Vec<(ActorId, PartnerQuota)>,
+    //La agencia que vendio cada boleto vendido por agencias
+    pub partner_tickets: Vec<(u128, ActorId)>,
+    //Las cuentas del personal de taquilla que pueden vender a nombre de otros
+    pub operators: Vec<ActorId>,
+    //Las ventas de cada operador de taquilla para cuadrar la caja
+    pub operator_sales: Vec<(ActorId, Vec<OperatorSale>)>,
+    //Las direcciones provisionales de los boletos de clientes sin cartera y quien los reclamo
+    pub custodial_claims: Vec<(ActorId, Option<ActorId>)>,
+    //Los compromisos para reclamar boletos de taquilla con el bloque en que se hicieron
+    pub claim_commitments: Vec<([u8; 32], u32)>,
+    //Quien pago cada boleto que se compro para alguien mas, a esa cuenta se le hacen los reembolsos
+    pub ticket_payers: Vec<(u128, ActorId)>,
+    //El historial de cambios al numero de boletos despues de crear el concierto
+    pub supply_changes: Vec<SupplyChange>,
+    //Hasta cuando los compradores pueden pedir su reembolso despues de cambiar la fecha
+    pub reschedule_refund_deadline: Option<u64>,
+    //Los avisos que mando el creador a los compradores
+    pub announcements: Vec<Announcement>,
+    //Las cuentas a las que falta entregar el ultimo aviso
+    pub announcement_queue: Vec<ActorId>,
+    //Los boletos que ya se usaron en la entrada con la cuenta que los tenia
+    pub redeemed_tickets: Vec<(u128, ActorId)>,
+    //El id del primer boleto del concierto actual, los boletos de conciertos anteriores tienen ids menores
+    pub first_ticket_id: u128,
+    //Las insignias de asistencia de cada cuenta de todos los conciertos
+    //Su token lo tiene el propio smart contract, que nunca lo transfiere, asi nadie las puede transferir
+    pub badges: Vec<(ActorId, Vec<AttendanceBadge>)>,
+    //El numero de insignias creadas, el id de cada insignia sale de aqui
+    pub badge_counter: u128,
+    //El id del NFT de cada boleto cuando el backend es TokenBackend::NonFungibleToken
+    pub nft_tokens: Vec<(u128, NftTokenId)>,
+    //Los boletos de compras abortadas cuyos tokens no se pudieron destruir
+    pub orphaned_tickets: Vec<u128>,
+    //Los tokens fungibles de boletos de taquilla que el concierto guarda hasta que se reclamen
+    pub held_tokens: u128,
+    //Lo que se pago por cada boleto, se usa para reembolsarlo
+    pub ticket_payments: Vec<(u128, TicketPayment)>,
+    //Los tokens fungibles de boletos reembolsados que se le regresaron al concierto
+    pub returned_tokens: u128,
 }
 
 //Aqui se guardaran los boletos
 pub type Tickets = Vec<(u128, Option<TokenMetadata>)>;
 
+//Es el id de un token en el programa de NFTs, lo asigna ese programa al mintear
+pub type NftTokenId = gear_lib::non_fungible_token::token::TokenId;
+
 
 #[doc(hidden)]
 impl State {
@@ -67,9 +181,42 @@ impl State {
             date: self.date,
             number_of_tickets: self.number_of_tickets,
             tickets_left: self.tickets_left,
+            price_phase: self.price_phase,
         }
     }
 
+    //Regresa los asientos de una seccion que todavia no se han vendido
+    pub fn available_seats(self, section: String) -> Vec<Seat> {
+        let sold: Vec<Seat> = self.sold_seats.into_iter().map(|(seat, _)| seat).collect();
+        self.seat_map
+            .into_iter()
+            .filter(|seat_section| seat_section.name == section)
+            .flat_map(|seat_section| seat_section.seats())
+            .filter(|seat| !sold.contains(seat))
+            .collect()
+    }
+
+    //Regresa el precio de un boleto en el momento indicado
+    pub fn current_price(self, timestamp: u64) -> u128 {
+        self.sale_mode.price_at(timestamp)
+    }
+
+    //Regresa las insignias de asistencia de una cuenta
+    pub fn user_badges(self, user: ActorId) -> Vec<AttendanceBadge> {
+        self.badges
+            .into_iter()
+            .find_map(|(account, badges)| (account == user).then_some(badges))
+            .unwrap_or_default()
+    }
+
+    //Regresa el lugar de la cuenta en la lista de espera, empezando en 1
+    pub fn waitlist_position(self, user: ActorId) -> Option<u32> {
+        self.waitlist
+            .iter()
+            .position(|(account, _)| *account == user)
+            .map(|position| position as u32 + 1)
+    }
+
     //Se hace la asignación de la metadata de los boletos a cada comprador
     pub fn user_tickets(self, user: ActorId) -> Vec<Option<TokenMetadata>> {
         self.metadata
@@ -82,6 +229,533 @@ impl State {
     }
 }
 
+//Es el tipo de programa donde se crean los boletos, se elige al inicializar el smart contract
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum TokenBackend {
+    //Un contrato GMT-1155 de github.com/gear-dapps/multitoken
+    #[default]
+    Multitoken,
+    //Un contrato de NFTs de github.com/gear-dapps/non-fungible-token
+    NonFungibleToken,
+    //Un registro dentro del propio smart contract, no necesita otro programa
+    Internal,
+}
+
+//Es una operacion con el programa de tokens que espera su turno en la cola,
+//o cuya respuesta no llego a tiempo o fue un error
+//Cualquier cuenta puede continuar la primera de la cola y el dueño del smart contract puede abortarla
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PendingOperation {
+    //El id de la operacion, cada operacion tiene uno distinto
+    pub id: u64,
+    //El id del mensaje que esta haciendo la operacion
+    pub message_id: MessageId,
+    //La cuenta que pidio la operacion, se le avisa a su buzon cuando la completa otro mensaje
+    pub account: ActorId,
+    //La operacion que quedo pendiente
+    pub kind: PendingOperationKind,
+    //Dice si ningun mensaje esta haciendo la operacion, porque espera su turno en la cola,
+    //porque el mensaje fue interrumpido por falta de gas o por agotar su tiempo de espera,
+    //o porque termino sin completarla
+    pub interrupted: bool,
+}
+
+//Son las operaciones que pueden quedar pendientes
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum PendingOperationKind {
+    //El minteo de los tokens de una compra de boletos
+    Mint {
+        buyer: ActorId,
+        //Quien pago la compra, puede ser distinto de quien recibe los boletos
+        payer: ActorId,
+        amount: u128,
+        ticket_ids: Vec<u128>,
+        request_id: Option<u128>,
+        //El valor que pago el comprador, se le regresa si la compra se aborta
+        paid: u128,
+        //La comision de la plataforma que se cobro de lo pagado
+        fee: u128,
+        //El hash del codigo de descuento que se uso, sus usos se regresan si la compra se aborta
+        promo_code: Option<[u8; 32]>,
+        //Dice si el programa de tokens ya confirmo el minteo y solo falta entregar los tokens
+        minted: bool,
+    },
+    //El minteo del token de la insignia de asistencia de una cuenta
+    Badge {
+        holder: ActorId,
+        token_id: u128,
+    },
+    //La entrega de los boletos de taquilla a la cuenta que los reclamo
+    Claim {
+        claimer: ActorId,
+        //La direccion provisional donde estaban los boletos
+        account: ActorId,
+        ticket_ids: Vec<u128>,
+    },
+    //La devolucion de los tokens de unos boletos que se van a reembolsar
+    Refund {
+        holder: ActorId,
+        ticket_ids: Vec<u128>,
+    },
+    //La conversion de los boletos en NFTs, guarda lo que ya se hizo para poder continuarla
+    //minted son los compradores cuyos NFTs ya se crearon y converted los que ya los recibieron
+    Hold {
+        minted: Vec<ActorId>,
+        converted: Vec<ActorId>,
+    },
+}
+
+impl PendingOperationKind {
+    //Regresa los boletos que mueve la operacion, mientras esta en la cola no se pueden usar ni reembolsar
+    pub fn ticket_ids(&self) -> &[u128] {
+        match self {
+            PendingOperationKind::Mint { ticket_ids, .. }
+            | PendingOperationKind::Claim { ticket_ids, .. }
+            | PendingOperationKind::Refund { ticket_ids, .. } => ticket_ids,
+            PendingOperationKind::Badge { .. } | PendingOperationKind::Hold { .. } => &[],
+        }
+    }
+}
+
+//Es el registro de una compra hecha con un id de compra
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PurchaseReceipt {
+    pub concert_id: u128,
+    pub amount: u128,
+    //El momento de la compra, despues de un tiempo el registro se olvida
+    pub timestamp: u64,
+}
+
+//Es una seccion del lugar del concierto
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct SeatSection {
+    pub name: String,
+    //El numero de asientos de cada fila, el primer elemento es la fila 1
+    pub rows: Vec<u32>,
+}
+
+//Es un asiento del mapa de asientos, las filas y los asientos se cuentan desde 1
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, TypeInfo)]
+pub struct Seat {
+    pub section: String,
+    pub row: u32,
+    pub number: u32,
+}
+
+//Son los boletos apartados por un comprador hasta una fecha limite
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Reservation {
+    pub amount: u128,
+    //Los asientos apartados, esta vacio si el concierto no tiene mapa de asientos
+    pub seats: Vec<Seat>,
+    //El block timestamp en el que se liberan los boletos si no se termina la compra
+    pub expires_at: u64,
+}
+
+//Es la forma en la que se calcula el precio de los boletos
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum SaleMode {
+    //Todos los boletos cuestan lo mismo
+    FixedPrice {
+        price: u128,
+    },
+    //El precio baja linealmente de start_price a floor_price entre start_time y end_time
+    //Si uniform_price es verdadero todos pagan el precio de cierre y se les regresa la diferencia
+    DutchAuction {
+        start_price: u128,
+        floor_price: u128,
+        start_time: u64,
+        end_time: u64,
+        uniform_price: bool,
+    },
+    //El precio cambia por fases, por ejemplo preventa, precio normal y precio en taquilla
+    //Las fases van ordenadas por su inicio y cada una dura hasta que empieza la siguiente
+    Scheduled {
+        phases: Vec<PricePhase>,
+    },
+}
+
+//Es la comision que cobra el dueño del smart contract por cada venta
+//Se descuenta de lo que recibe el organizador, el comprador paga el precio del boleto
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum PlatformFee {
+    //Un porcentaje de lo que se cobro, de 0 a 100
+    Percentage(u8),
+    //Una cantidad fija por boleto, nunca mayor a lo que se cobro
+    FixedPerTicket(u128),
+}
+
+impl PlatformFee {
+    //Calcula la comision de una venta
+    pub fn fee_for(&self, cost: u128, tickets: u128) -> u128 {
+        match *self {
+            PlatformFee::Percentage(percent) => cost * percent as u128 / 100,
+            PlatformFee::FixedPerTicket(fee) => (fee * tickets).min(cost),
+        }
+    }
+}
+
+//Es una fase de precio de la venta
+#[derive(Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode, TypeInfo)]
+pub struct PricePhase {
+    pub name: String,
+    pub price: u128,
+    pub starts_at: u64,
+}
+
+impl Default for SaleMode {
+    fn default() -> Self {
+        SaleMode::FixedPrice { price: 0 }
+    }
+}
+
+impl SaleMode {
+    //Calcula el precio de un boleto en el momento indicado
+    pub fn price_at(&self, timestamp: u64) -> u128 {
+        match *self {
+            SaleMode::FixedPrice { price } => price,
+            //Antes de la primera fase se muestra el precio de la primera fase
+            SaleMode::Scheduled { ref phases } => self
+                .phase_at(timestamp)
+                .or_else(|| phases.first())
+                .map(|phase| phase.price)
+                .unwrap_or_default(),
+            SaleMode::DutchAuction {
+                start_price,
+                floor_price,
+                start_time,
+                end_time,
+                ..
+            } => {
+                if timestamp <= start_time {
+                    start_price
+                } else if timestamp >= end_time {
+                    floor_price
+                } else {
+                    let elapsed = (timestamp - start_time) as u128;
+                    let duration = (end_time - start_time) as u128;
+                    start_price - (start_price - floor_price) * elapsed / duration
+                }
+            }
+        }
+    }
+
+    //Regresa la fase de precio activa en el momento indicado
+    pub fn phase_at(&self, timestamp: u64) -> Option<&PricePhase> {
+        match self {
+            SaleMode::Scheduled { phases } => phases
+                .iter()
+                .rev()
+                .find(|phase| phase.starts_at <= timestamp),
+            _ => None,
+        }
+    }
+
+    //Dice si es una subasta donde todos pagan el precio de cierre
+    pub fn is_uniform_auction(&self) -> bool {
+        matches!(
+            self,
+            SaleMode::DutchAuction {
+                uniform_price: true,
+                ..
+            }
+        )
+    }
+}
+
+//Son los boletos y el valor que pago un comprador en la subasta
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct AuctionPayment {
+    pub tickets: u128,
+    pub paid: u128,
+}
+
+//Es la cuota de boletos de una agencia socia y su reporte de ventas
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PartnerQuota {
+    pub quota: u128,
+    pub sold: u128,
+    //Lo que pago la agencia por los boletos vendidos
+    pub revenue: u128,
+}
+
+impl PartnerQuota {
+    pub fn remaining(&self) -> u128 {
+        self.quota - self.sold
+    }
+}
+
+//Es la prueba de que una cuenta asistio a un concierto
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct AttendanceBadge {
+    pub concert_id: u128,
+    //El id del token de la insignia en el programa de tokens
+    pub token_id: u128,
+    pub name: String,
+    pub date: u128,
+}
+
+impl AttendanceBadge {
+    //La metadata del token de la insignia sale del nombre y la fecha del concierto
+    pub fn metadata(&self) -> TokenMetadata {
+        TokenMetadata {
+            title: Some(format!("Asistencia: {}", self.name)),
+            description: Some(format!("Asistio a {} el {}", self.name, self.date)),
+            media: None,
+            reference: None,
+        }
+    }
+}
+
+//Es un aviso del creador para los compradores
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Announcement {
+    pub message: String,
+    pub timestamp: u64,
+    //El numero de cuentas que deben recibir el aviso y a cuantas ya se les entrego
+    pub recipients: u32,
+    pub delivered: u32,
+}
+
+//Es un cambio al numero de boletos del concierto
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct SupplyChange {
+    pub previous_supply: u128,
+    pub new_supply: u128,
+    pub timestamp: u64,
+}
+
+//Es quien recibe los boletos cuando no es quien los paga
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum TicketRecipient {
+    //Una cuenta que ya existe, por ejemplo para regalar boletos
+    Account(ActorId),
+    //Un cliente sin cartera, solo para los operadores de taquilla
+    //Los boletos se guardan en una direccion provisional
+    //hasta que alguien los reclama con el secreto, claim_hash se calcula con claim_secret_hash
+    Custodial { claim_hash: [u8; 32] },
+}
+
+//Es una venta hecha por un operador de taquilla
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct OperatorSale {
+    pub recipient: ActorId,
+    pub ticket_ids: Vec<u128>,
+    pub paid: u128,
+    pub timestamp: u64,
+}
+
+//Calcula el hash del secreto con el que se reclaman boletos guardados en taquilla
+pub fn claim_secret_hash(secret: &[u8; 32]) -> [u8; 32] {
+    blake2_256(secret)
+}
+
+//Es la direccion provisional donde se guardan los boletos de un cliente sin cartera
+pub fn custodial_account(claim_hash: [u8; 32]) -> ActorId {
+    ActorId::new(claim_hash)
+}
+
+//Es lo que un comprador tiene guardado en el smart contract por sus boletos
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct EscrowPayment {
+    pub paid: u128,
+    //La parte de lo pagado que corresponde a la comision de la plataforma
+    pub fee: u128,
+}
+
+//Es lo que se pago por un boleto, con esto se reembolsa a quien lo pago
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct TicketPayment {
+    pub payer: ActorId,
+    pub paid: u128,
+    //La parte de lo pagado que corresponde a la comision de la plataforma
+    pub fee: u128,
+    //El hash del codigo de descuento que se uso, su uso se regresa con el reembolso
+    pub promo_code: Option<[u8; 32]>,
+}
+
+//Es una subasta de ofertas selladas para boletos premium apartados de la venta al publico
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct SealedAuction {
+    //Los boletos apartados para la subasta
+    pub tickets: u128,
+    //Los asientos apartados, se reparten en orden entre los ganadores
+    pub seats: Vec<Seat>,
+    //El ultimo momento para mandar ofertas
+    pub commit_deadline: u64,
+    //El ultimo momento para revelar las ofertas
+    pub reveal_deadline: u64,
+    pub bids: Vec<SealedBid>,
+    pub settled: bool,
+}
+
+//Es una oferta de la subasta, el monto solo se conoce cuando se revela
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct SealedBid {
+    pub bidder: ActorId,
+    //Se calcula con bid_commitment(monto, sal, postor)
+    pub commitment: [u8; 32],
+    pub deposit: u128,
+    pub amount: Option<u128>,
+    pub metadata: Option<TokenMetadata>,
+}
+
+//Es un boleto ganado en la subasta que ya esta pagado
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct AuctionWin {
+    pub paid: u128,
+    //La comision de la plataforma que se cobro de lo pagado
+    pub fee: u128,
+    pub seat: Option<Seat>,
+    pub metadata: Option<TokenMetadata>,
+}
+
+//Es un codigo de descuento, solo se guarda el hash del codigo
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PromoCode {
+    //El porcentaje que se descuenta del precio, de 1 a 100
+    pub discount_percent: u8,
+    //Los boletos que se pueden comprar con el codigo, cada boleto cuenta como un uso
+    pub max_uses: u32,
+    pub uses: u32,
+    //Despues de este momento el codigo ya no sirve
+    pub expires_at: u64,
+}
+
+impl PromoCode {
+    pub fn remaining_uses(&self) -> u32 {
+        self.max_uses.saturating_sub(self.uses)
+    }
+}
+
+//Calcula el hash con el que se registra un codigo de descuento en un concierto
+//El id del concierto sirve de sal, asi los hashes no se pueden precalcular para todos los conciertos
+pub fn promo_code_hash(concert_id: u128, code: &str) -> [u8; 32] {
+    let mut data = concert_id.to_le_bytes().to_vec();
+    data.extend_from_slice(code.as_bytes());
+    blake2_256(&data)
+}
+
+//Calcula el compromiso de una oferta sellada
+pub fn bid_commitment(amount: u128, salt: &[u8; 32], bidder: ActorId) -> [u8; 32] {
+    let mut data = amount.to_le_bytes().to_vec();
+    data.extend_from_slice(salt);
+    data.extend_from_slice(bidder.as_ref());
+    blake2_256(&data)
+}
+
+//Es un sorteo para repartir el derecho de compra cuando hay mas interesados que boletos
+//Todo lo necesario para repetir el sorteo queda guardado aqui
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Lottery {
+    //Los boletos que puede comprar cada ganador
+    pub tickets_per_winner: u128,
+    //El compromiso de la semilla del creador, calculado con commitment(semilla, creador)
+    pub seed_commitment: [u8; 32],
+    pub phase: LotteryPhase,
+    //Hasta cuando se pueden revelar los secretos, se fija al cerrar el registro
+    //Despues de esta fecha cualquiera puede hacer el sorteo sin la semilla del creador
+    pub reveal_deadline: Option<u64>,
+    //La garantia que deposita el creador al abrir el sorteo, se le regresa si revela su semilla a tiempo
+    //Si no la revela se reparte entre los participantes que revelaron su secreto
+    pub creator_bond: u128,
+    //Los participantes en orden de registro
+    pub entries: Vec<LotteryEntry>,
+    //La semilla final del sorteo, combina la semilla del creador con los secretos revelados
+    pub seed: Option<[u8; 32]>,
+    pub winners: Vec<ActorId>,
+}
+
+//Son las fases del sorteo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum LotteryPhase {
+    //Los participantes se registran con el compromiso de su secreto
+    Registration,
+    //Los participantes revelan su secreto, los que no lo revelan no entran al sorteo
+    Reveal,
+    //Se eligieron los ganadores, con la semilla del creador o sin ella despues del plazo para revelar
+    Drawn,
+}
+
+//Es el registro de un participante del sorteo
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct LotteryEntry {
+    pub participant: ActorId,
+    pub commitment: [u8; 32],
+    pub secret: Option<[u8; 32]>,
+}
+
+//Calcula el compromiso de un secreto para una cuenta
+//Incluir la cuenta evita que alguien copie el compromiso de otro
+pub fn commitment(secret: &[u8; 32], account: ActorId) -> [u8; 32] {
+    let mut data = secret.to_vec();
+    data.extend_from_slice(account.as_ref());
+    blake2_256(&data)
+}
+
+//Es la semilla del creador que se usa cuando el sorteo se hace sin el despues del plazo para revelar
+pub const FORCED_LOTTERY_SEED: [u8; 32] = [0; 32];
+
+//Calcula la semilla final del sorteo con la semilla del creador y los secretos revelados en orden
+pub fn lottery_seed(creator_seed: &[u8; 32], entries: &[LotteryEntry]) -> [u8; 32] {
+    let mut data = creator_seed.to_vec();
+    for secret in entries.iter().filter_map(|entry| entry.secret) {
+        data.extend_from_slice(&secret);
+    }
+    blake2_256(&data)
+}
+
+//Elige a los ganadores del sorteo a partir de la semilla final
+//En cada ronda se saca un candidato usando el hash de la semilla con el numero de ronda
+pub fn draw_winners(seed: &[u8; 32], mut candidates: Vec<ActorId>, winners: usize) -> Vec<ActorId> {
+    let mut drawn = Vec::with_capacity(winners);
+    let mut round: u64 = 0;
+    while drawn.len() < winners && !candidates.is_empty() {
+        let mut data = seed.to_vec();
+        data.extend_from_slice(&round.to_le_bytes());
+        let hash = blake2_256(&data);
+        let mut random = [0u8; 8];
+        random.copy_from_slice(&hash[..8]);
+        let index = (u64::from_le_bytes(random) % candidates.len() as u64) as usize;
+        drawn.push(candidates.remove(index));
+        round += 1;
+    }
+    drawn
+}
+
+impl SeatSection {
+    //El numero total de asientos de la seccion
+    pub fn capacity(&self) -> u128 {
+        self.rows.iter().map(|seats| *seats as u128).sum()
+    }
+
+    //Dice si el asiento pertenece a la seccion
+    pub fn contains(&self, seat: &Seat) -> bool {
+        seat.section == self.name
+            && seat.row >= 1
+            && seat.number >= 1
+            && self
+                .rows
+                .get(seat.row as usize - 1)
+                .map_or(false, |seats| seat.number <= *seats)
+    }
+
+    //Regresa todos los asientos de la seccion
+    pub fn seats(&self) -> Vec<Seat> {
+        self.rows
+            .iter()
+            .enumerate()
+            .flat_map(|(row, seats)| {
+                (1..=*seats).map(move |number| Seat {
+                    section: self.name.clone(),
+                    row: row as u32 + 1,
+                    number,
+                })
+            })
+            .collect()
+    }
+}
+
 //La estructura representa el estado actual del evento
 #[derive(Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode, TypeInfo)]
 pub struct CurrentConcert {
@@ -90,26 +764,216 @@ pub struct CurrentConcert {
     pub date: u128,
     pub number_of_tickets: u128,
     pub tickets_left: u128,
+    //La fase de precio activa, solo cuando el precio va por fases
+    pub price_phase: Option<PricePhase>,
 }
 
 // Definimos las acciones posibles en el smart contract
 #[derive(Debug, Encode, Decode, TypeInfo)]
 pub enum ConcertAction {
     //Crear un evento
+    //Si se da un mapa de asientos, el numero de boletos debe ser igual al numero de asientos
+    //Los porcentajes de revenue_split deben sumar 100, si esta vacio todo es para el creador
+    //Lo recaudado se libera dispute_period despues del hold
+    //complimentary_tickets son los boletos de cortesia, no cuentan en number_of_tickets
     Create {
         creator: ActorId,
         name: String,
         description: String,
         number_of_tickets: u128,
         date: u128,
+        seat_map: Vec<SeatSection>,
+        sale_mode: SaleMode,
+        revenue_split: Vec<(ActorId, u8)>,
+        dispute_period: u64,
+        complimentary_tickets: u128,
     },
     //Convertir los tokens a NFTs
     Hold,
-    //Realiza la compra de boletos
+    //El creador da boletos de cortesia sin pago, se toman de los boletos de cortesia
+    IssueComplimentary {
+        to: ActorId,
+        amount: u128,
+        metadata: Vec<Option<TokenMetadata>>,
+    },
+    //El creador agrega o quita a un operador de taquilla
+    SetOperator {
+        operator: ActorId,
+        enabled: bool,
+    },
+    //Antes de reclamar sus boletos el cliente se compromete con commitment(secreto, su cuenta)
+    //Asi quien vea el secreto en ClaimTickets no puede reclamar los boletos antes que el
+    CommitClaim {
+        commitment: [u8; 32],
+    },
+    //Un cliente reclama con el secreto los boletos que compro en taquilla, solo se puede una vez
+    //Necesita el compromiso de su cuenta hecho con CommitClaim en un bloque anterior
+    ClaimTickets {
+        secret: [u8; 32],
+    },
+    //El creador cambia los datos del concierto, los campos en None no cambian
+    //Si cambia la fecha los compradores pueden pedir su reembolso por un tiempo
+    UpdateConcert {
+        name: Option<String>,
+        description: Option<String>,
+        date: Option<u128>,
+    },
+    //Quien tiene boletos los regresa despues de que cambio la fecha y se le reembolsa a quien los pago
+    //Los boletos se cancelan y vuelven a la venta, antes tiene que aprobar al concierto en el programa de tokens
+    RequestRescheduleRefund,
+    //El creador manda un aviso al buzon de cada comprador
+    //Se entrega por partes, si quedan cuentas pendientes se continua con DeliverAnnouncement
+    Announce {
+        message: String,
+    },
+    //Entrega el aviso pendiente a la siguiente parte de los compradores
+    DeliverAnnouncement,
+    //El creador o un operador de taquilla marca un boleto como usado en la entrada
+    RedeemTicket {
+        ticket_id: u128,
+    },
+    //Quien uso un boleto en la entrada reclama su insignia de asistencia
+    //El token de la insignia se crea a nombre del smart contract, que lo guarda para que no se pueda transferir
+    ClaimBadge,
+    //El creador abre mas boletos a la venta
+    IncreaseSupply {
+        amount: u128,
+    },
+    //El creador quita boletos de la venta, solo de los que no se han vendido
+    DecreaseSupply {
+        amount: u128,
+    },
+    //El creador asigna la cuota total de boletos de una agencia, se apartan de la venta al publico
+    AssignPartnerQuota {
+        partner: ActorId,
+        quota: u128,
+    },
+    //Una agencia vende boletos de su cuota a un cliente, los paga con el valor del mensaje
+    PartnerSell {
+        to: ActorId,
+        amount: u128,
+        metadata: Vec<Option<TokenMetadata>>,
+    },
+    //Realiza la compra de boletos, se pagan con el valor del mensaje y se regresa lo que sobre
+    //Si se manda un request_id que ya se uso, se responde con la compra original sin volver a comprar
+    //Si el concierto tiene mapa de asientos se debe elegir un asiento por boleto
+    //Si se manda un codigo de descuento valido se descuenta del precio de los boletos
     BuyTickets {
         amount: u128,
         metadata: Vec<Option<TokenMetadata>>,
+        request_id: Option<u128>,
+        seats: Vec<Seat>,
+        promo_code: Option<String>,
+        //Si se da, los boletos son de recipient y quien manda el mensaje queda como quien pago
+        recipient: Option<TicketRecipient>,
+    },
+    //Aparta boletos por un tiempo limitado para terminar el pago despues
+    ReserveTickets {
+        amount: u128,
+        seats: Vec<Seat>,
+    },
+    //Termina la compra de los boletos apartados
+    //Tambien sirve para comprar los boletos ofrecidos a la lista de espera
+    CompletePurchase {
+        metadata: Vec<Option<TokenMetadata>>,
+    },
+    //Se forma en la lista de espera cuando no hay suficientes boletos
+    JoinWaitlist {
+        amount: u128,
     },
+    //El creador abre el registro al sorteo, mientras tanto no se venden boletos al publico
+    //El valor del mensaje es la garantia del creador, la pierde si no revela su semilla a tiempo
+    OpenLottery {
+        tickets_per_winner: u128,
+        seed_commitment: [u8; 32],
+    },
+    //Un participante se registra al sorteo con el compromiso de su secreto
+    EnterLottery {
+        commitment: [u8; 32],
+    },
+    //El creador cierra el registro y empieza la fase de revelar secretos
+    CloseLotteryRegistration,
+    //Un participante revela su secreto
+    RevealLotteryEntry {
+        secret: [u8; 32],
+    },
+    //El creador revela su semilla y se eligen los ganadores
+    DrawLottery {
+        seed: [u8; 32],
+    },
+    //Si el creador no revelo su semilla a tiempo cualquiera hace el sorteo solo con los secretos revelados
+    //Se usa la semilla FORCED_LOTTERY_SEED, si nadie revelo no hay ganadores y la venta al publico sigue
+    //La garantia del creador se reparte entre los que revelaron, si nadie revelo queda como comision de la plataforma
+    ForceDrawLottery,
+    //Regresa lo que se pago por arriba del precio de cierre en una subasta con precio uniforme
+    ClaimAuctionRefund,
+    //Un beneficiario retira su parte de lo recaudado cuando termina el periodo de disputa
+    //Tambien retira lo que no retiro de conciertos anteriores, eso se puede retirar en cualquier momento
+    Withdraw,
+    //Durante el periodo de disputa quien tiene unos boletos los regresa y se le reembolsa a quien los pago
+    //Antes tiene que aprobar al concierto en el programa de tokens para que pueda recuperar los tokens
+    RequestRefund {
+        ticket_ids: Vec<u128>,
+    },
+    //El creador o el dueño cancelan el concierto y se reembolsa a todos los compradores
+    CancelConcert,
+    //El creador aparta boletos para una subasta de ofertas selladas
+    //Si el concierto tiene mapa de asientos se da un asiento por boleto
+    StartSealedBidAuction {
+        tickets: u128,
+        seats: Vec<Seat>,
+        commit_deadline: u64,
+        reveal_deadline: u64,
+    },
+    //Manda el compromiso de una oferta, el valor del mensaje es el deposito
+    CommitBid {
+        commitment: [u8; 32],
+    },
+    //Revela la oferta con la metadata del boleto que se quiere
+    RevealBid {
+        amount: u128,
+        salt: [u8; 32],
+        metadata: Option<TokenMetadata>,
+    },
+    //Elige a los ganadores y regresa los depositos
+    SettleSealedBidAuction,
+    //El ganador de la subasta recibe su boleto
+    ClaimAuctionTicket,
+    //El creador registra un codigo de descuento con el hash calculado con promo_code_hash
+    AddPromoCode {
+        code_hash: [u8; 32],
+        discount_percent: u8,
+        max_uses: u32,
+        expires_at: u64,
+    },
+    //Continua la primera operacion de la cola con el programa de tokens y despues las que esperan su turno
+    //Cualquier cuenta la puede continuar cuando ningun mensaje la esta haciendo
+    RetryPendingOperation,
+    //Descarta la primera operacion de la cola con el programa de tokens
+    AbortPendingOperation,
+    //Detiene las acciones que cambian el estado, solo la puede usar el dueño
+    Pause {
+        reason: String,
+    },
+    //Vuelve a permitir las acciones que cambian el estado
+    Unpause,
+    //El dueño propone una nueva direccion como dueño del smart contract
+    ProposeOwner {
+        new_owner: ActorId,
+    },
+    //La direccion propuesta acepta ser el nuevo dueño
+    AcceptOwnership,
+    //Cambia el programa de tokens, solo se puede cuando no hay boletos vendidos sin convertir
+    SetTokenContract {
+        contract_id: ActorId,
+    },
+    //El dueño cambia la comision de la plataforma, None quita la comision
+    //Se cobra en las ventas primarias, el smart contract no tiene reventa de boletos
+    SetPlatformFee {
+        fee: Option<PlatformFee>,
+    },
+    //El dueño retira las comisiones cobradas cuando no hay un concierto activo
+    WithdrawFees,
 }
 
 //Representa los eventos del smart contract
@@ -131,6 +995,201 @@ pub enum ConcertEvent {
         concert_id: u128,
         amount: u128,
     },
+    //Guarda la informacion de la accion reservetickets
+    Reserved {
+        concert_id: u128,
+        amount: u128,
+        expires_at: u64,
+    },
+    //Guarda la informacion de la accion joinwaitlist
+    JoinedWaitlist {
+        concert_id: u128,
+        position: u32,
+    },
+    //Se manda al buzon de la cuenta de la lista de espera cuando se le apartan boletos
+    WaitlistOffer {
+        concert_id: u128,
+        amount: u128,
+        expires_at: u64,
+    },
+    //Guarda la informacion de la accion openlottery
+    LotteryOpened {
+        concert_id: u128,
+    },
+    //Guarda la informacion de la accion enterlottery
+    LotteryEntered {
+        concert_id: u128,
+    },
+    //Guarda la informacion de la accion closelotteryregistration
+    LotteryRegistrationClosed {
+        concert_id: u128,
+    },
+    //Guarda la informacion de la accion reveallotteryentry
+    LotteryEntryRevealed {
+        concert_id: u128,
+    },
+    //Guarda la informacion de la accion drawlottery
+    LotteryDrawn {
+        concert_id: u128,
+        winners: Vec<ActorId>,
+    },
+    //Se manda al buzon de cada ganador del sorteo con los boletos que tiene apartados
+    LotteryWon {
+        concert_id: u128,
+        amount: u128,
+        expires_at: u64,
+    },
+    //Guarda la informacion de la accion claimauctionrefund
+    AuctionRefund {
+        concert_id: u128,
+        amount: u128,
+    },
+    //Guarda la informacion de la accion setoperator
+    OperatorSet {
+        operator: ActorId,
+        enabled: bool,
+    },
+    //Guarda la informacion de la accion commitclaim
+    ClaimCommitted {
+        concert_id: u128,
+    },
+    //Guarda la informacion de la accion claimtickets
+    TicketsClaimed {
+        concert_id: u128,
+        amount: u128,
+    },
+    //Guarda la informacion de la accion updateconcert cuando no cambia la fecha
+    ConcertUpdated {
+        concert_id: u128,
+    },
+    //Guarda la informacion de la accion updateconcert cuando cambia la fecha
+    Rescheduled {
+        concert_id: u128,
+        previous_date: u128,
+        new_date: u128,
+        refund_deadline: u64,
+    },
+    //Guarda la informacion de la accion requestreschedulerefund
+    RescheduleRefund {
+        concert_id: u128,
+        amount: u128,
+        tickets: u128,
+    },
+    //Se manda al buzon de cada comprador con el aviso del creador
+    Announcement {
+        concert_id: u128,
+        message: String,
+    },
+    //Guarda la informacion de las acciones announce y deliverannouncement
+    AnnouncementDelivered {
+        concert_id: u128,
+        delivered: u32,
+        remaining: u32,
+    },
+    //Guarda la informacion de la accion redeemticket
+    TicketRedeemed {
+        concert_id: u128,
+        ticket_id: u128,
+        holder: ActorId,
+    },
+    //Guarda la informacion de la accion claimbadge
+    BadgeClaimed {
+        concert_id: u128,
+        token_id: u128,
+    },
+    //Guarda la informacion de las acciones increasesupply y decreasesupply
+    SupplyChanged {
+        concert_id: u128,
+        previous_supply: u128,
+        new_supply: u128,
+    },
+    //Guarda la informacion de la accion assignpartnerquota
+    PartnerQuotaAssigned {
+        concert_id: u128,
+        partner: ActorId,
+        quota: u128,
+    },
+    //Guarda la informacion de la accion cancelconcert con lo que se reembolso
+    ConcertCancelled {
+        concert_id: u128,
+        refunded: u128,
+    },
+    //Guarda la informacion de la accion withdraw
+    Withdrawn {
+        concert_id: u128,
+        amount: u128,
+    },
+    //Guarda la informacion de la accion requestrefund
+    Refunded {
+        concert_id: u128,
+        amount: u128,
+        tickets: u128,
+    },
+    //Guarda la informacion de la accion startsealedbidauction
+    SealedBidAuctionStarted {
+        concert_id: u128,
+        tickets: u128,
+    },
+    //Guarda la informacion de la accion commitbid
+    BidCommitted {
+        concert_id: u128,
+    },
+    //Guarda la informacion de la accion revealbid
+    BidRevealed {
+        concert_id: u128,
+        amount: u128,
+    },
+    //Guarda la informacion de la accion settlesealedbidauction con los ganadores y lo que pagan
+    SealedBidAuctionSettled {
+        concert_id: u128,
+        winners: Vec<(ActorId, u128)>,
+    },
+    //Guarda la informacion de la accion addpromocode
+    PromoCodeAdded {
+        concert_id: u128,
+        code_hash: [u8; 32],
+    },
+    //La operacion con el programa de tokens espera su turno en la cola, o no se confirmo y quedo pendiente
+    OperationPending {
+        concert_id: u128,
+        kind: PendingOperationKind,
+    },
+    //La operacion pendiente se completo al reintentarla
+    OperationResolved {
+        concert_id: u128,
+    },
+    //La operacion pendiente fue descartada
+    OperationAborted {
+        concert_id: u128,
+    },
+    //El dueño pauso el smart contract
+    Paused {
+        reason: String,
+    },
+    //El dueño quito la pausa del smart contract
+    Unpaused,
+    //Se propuso un nuevo dueño
+    OwnerProposed {
+        new_owner: ActorId,
+    },
+    //El nuevo dueño acepto y ahora es el dueño del smart contract
+    OwnershipTransferred {
+        previous_owner: ActorId,
+        new_owner: ActorId,
+    },
+    //Se cambio el programa de tokens
+    TokenContractChanged {
+        previous_contract: ActorId,
+        new_contract: ActorId,
+    },
+    //Se cambio la comision de la plataforma
+    PlatformFeeChanged {
+        fee: Option<PlatformFee>,
+    },
+    //El dueño retiro las comisiones
+    FeesWithdrawn {
+        amount: u128,
+    },
 }
 
 //Son las queries que pueden entrar dentro del estado del smart contract
@@ -153,5 +1212,7 @@ pub enum ConcertStateReply {
 #[derive(Debug, Encode, Decode, TypeInfo)]
 pub struct InitConcert {
     pub owner_id: ActorId,
+    //La direccion del programa de tokens, no se usa con TokenBackend::Internal
     pub mtk_contract: ActorId,
+    pub backend: TokenBackend,
 }
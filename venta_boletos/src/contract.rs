@@ -2,16 +2,45 @@
 
 use venta_boletos_io::*;
 use gear_lib::multitoken::io::*;
-use gstd::{errors::Result, msg, prelude::*, ActorId, MessageId};
+use gstd::{errors::Result, exec, msg, prelude::*, ActorId, MessageId};
 use hashbrown::{HashMap, HashSet};
-use multitoken_io::MyMTKAction;
+
+//Aqui esta la comunicacion con el programa donde se crean los boletos
+mod token;
 
 const ZERO_ID: ActorId = ActorId::zero();
+//El gas que se reserva en cada mensaje para poder ejecutar handle_signal
+const SIGNAL_GAS_RESERVE: u64 = 1_000_000_000;
+//El numero maximo de operaciones de la cola que continua un mensaje despues de completar la suya
+const QUEUED_OPERATIONS_PER_MESSAGE: usize = 5;
+//El tiempo en milisegundos que recordamos los ids de compra de cada comprador
+const REQUEST_ID_TTL: u64 = 24 * 60 * 60 * 1000;
+//El tiempo en milisegundos que se apartan los boletos de una reservacion
+const RESERVATION_PERIOD: u64 = 10 * 60 * 1000;
+//El numero maximo de boletos que aparta una cuenta en una reservacion
+const MAX_TICKETS_PER_RESERVATION: u128 = 10;
+//El tiempo en milisegundos que tiene alguien de la lista de espera para comprar los boletos que se le ofrecen
+const WAITLIST_OFFER_PERIOD: u64 = 30 * 60 * 1000;
+//El tiempo en milisegundos que tienen los participantes del sorteo para revelar sus secretos
+//y el creador su semilla, despues cualquiera puede hacer el sorteo
+const LOTTERY_REVEAL_PERIOD: u64 = 24 * 60 * 60 * 1000;
+//El tiempo en milisegundos que tienen los ganadores del sorteo para comprar sus boletos
+const LOTTERY_CLAIM_PERIOD: u64 = 24 * 60 * 60 * 1000;
+//El tiempo en milisegundos que tienen los compradores para pedir su reembolso cuando cambia la fecha
+const RESCHEDULE_REFUND_PERIOD: u64 = 7 * 24 * 60 * 60 * 1000;
+//El numero de buzones a los que se entrega un aviso en cada mensaje, para no pasar el limite de gas
+const ANNOUNCEMENT_BATCH_SIZE: usize = 50;
+//El largo maximo en bytes de un aviso
+const MAX_ANNOUNCEMENT_LENGTH: usize = 280;
+//El id del token de la primera insignia de asistencia, los siguientes van bajando
+//para no chocar con los ids de los conciertos y los boletos, que van subiendo
+const BADGE_TOKEN_ID_START: u128 = u64::MAX as u128;
 
 #[derive(Default)]
 //La explicacion de cada elemento de esta estructura esta en io/lib.rs en la estrcutura State
 struct Concert {
     owner_id: ActorId,
+    backend: TokenBackend,
     contract_id: ActorId,
     name: String,
     description: String,
@@ -26,6 +55,77 @@ struct Concert {
     running: bool,
     //Aqui la metadata sera un hasmap donde se guardaran los boletos correspondientes a cada comprador
     metadata: HashMap<ActorId, HashMap<u128, Option<TokenMetadata>>>,
+    pending_operations: Vec<PendingOperation>,
+    operation_counter: u64,
+    purchase_requests: HashMap<ActorId, HashMap<u128, PurchaseReceipt>>,
+    pause_reason: Option<String>,
+    pending_owner: Option<ActorId>,
+    //El registro interno de boletos que se usa cuando el backend es TokenBackend::Internal
+    ledger: HashMap<ActorId, u128>,
+    ledger_nfts: HashMap<u128, ActorId>,
+    seat_map: Vec<SeatSection>,
+    sold_seats: HashMap<Seat, u128>,
+    reservations: HashMap<ActorId, Reservation>,
+    reservation_cooldowns: HashMap<ActorId, u64>,
+    //La lista de espera en orden de llegada con los boletos que quiere cada cuenta
+    waitlist: Vec<(ActorId, u128)>,
+    lottery: Option<Lottery>,
+    sale_mode: SaleMode,
+    //El valor cobrado por la venta de boletos que guarda el smart contract
+    proceeds: u128,
+    clearing_price: Option<u128>,
+    auction_payments: HashMap<ActorId, AuctionPayment>,
+    sealed_auction: Option<SealedAuction>,
+    //Los boletos ganados en la subasta de ofertas selladas que faltan de reclamar
+    auction_wins: HashMap<ActorId, AuctionWin>,
+    promo_codes: HashMap<[u8; 32], PromoCode>,
+    revenue_split: Vec<(ActorId, u8)>,
+    withdrawals: HashMap<ActorId, u128>,
+    carried_revenue: HashMap<ActorId, u128>,
+    platform_fee: Option<PlatformFee>,
+    fee_balance: u128,
+    total_fees: u128,
+    //Lo que pago cada comprador, se guarda hasta que se libera al organizador o se reembolsa
+    escrow: HashMap<ActorId, EscrowPayment>,
+    dispute_period: u64,
+    held_at: Option<u64>,
+    cancelled: bool,
+    complimentary_allocation: u128,
+    complimentary_left: u128,
+    complimentary_tickets: HashSet<u128>,
+    partner_quotas: HashMap<ActorId, PartnerQuota>,
+    partner_tickets: HashMap<u128, ActorId>,
+    operators: HashSet<ActorId>,
+    operator_sales: HashMap<ActorId, Vec<OperatorSale>>,
+    custodial_claims: HashMap<ActorId, Option<ActorId>>,
+    claim_commitments: HashMap<[u8; 32], u32>,
+    ticket_payers: HashMap<u128, ActorId>,
+    supply_changes: Vec<SupplyChange>,
+    reschedule_refund_deadline: Option<u64>,
+    announcements: Vec<Announcement>,
+    announcement_queue: Vec<ActorId>,
+    redeemed_tickets: HashMap<u128, ActorId>,
+    first_ticket_id: u128,
+    badges: HashMap<ActorId, Vec<AttendanceBadge>>,
+    badge_counter: u128,
+    nft_tokens: HashMap<u128, NftTokenId>,
+    orphaned_tickets: Vec<u128>,
+    held_tokens: u128,
+    ticket_payments: HashMap<u128, TicketPayment>,
+    returned_tokens: u128,
+}
+
+//De donde salen los boletos que se crean en una compra
+#[derive(Clone, Copy)]
+enum TicketOrigin {
+    //La venta al publico
+    Sale,
+    //Los boletos de cortesia del creador
+    Complimentary,
+    //La cuota de una agencia socia
+    Partner(ActorId),
+    //La venta al publico hecha por un operador de taquilla
+    Operator(ActorId),
 }
 
 //Creamos una instancia global de la estructura para compartirla entre las diversas funciones
@@ -37,6 +137,7 @@ unsafe extern "C" fn init() {
     let config: InitConcert = msg::load().expect("Unable to decode InitConfig");
     let concert = Concert {
         owner_id: config.owner_id,
+        backend: config.backend,
         contract_id: config.mtk_contract,
         ..Default::default()
     };
@@ -46,10 +147,27 @@ unsafe extern "C" fn init() {
 //Definimos la funcion principal, aqui manejamos las acciones entrantes
 #[gstd::async_main]
 async unsafe fn main() {
+    //Reservamos gas para que handle_signal se pueda ejecutar si el mensaje se interrumpe
+    exec::system_reserve_gas(SIGNAL_GAS_RESERVE).expect("Unable to reserve gas for signals");
     //Obtenemos la accion con msg::load
     let action: ConcertAction = msg::load().expect("Could not load Action");
     //Obtenemos una referencia mutable a la instancia de Concert, que es nuestro actor
     let concert: &mut Concert = unsafe { CONTRACT.get_or_insert(Default::default()) };
+    //Mientras el smart contract este pausado solo se permiten las acciones del dueño para atender la pausa
+    if concert.pause_reason.is_some()
+        && !matches!(
+            action,
+            ConcertAction::Unpause
+                | ConcertAction::RetryPendingOperation
+                | ConcertAction::AbortPendingOperation
+                | ConcertAction::ProposeOwner { .. }
+                | ConcertAction::AcceptOwnership
+                | ConcertAction::SetTokenContract { .. }
+        )
+    {
+        panic!("CONCERT: The contract is paused");
+    }
+    concert.release_expired_reservations();
     match action {
         ConcertAction::Create {
             creator,
@@ -57,11 +175,109 @@ async unsafe fn main() {
             description,
             number_of_tickets,
             date,
-        } => concert.create_concert(name, description, creator, number_of_tickets, date),
+            seat_map,
+            sale_mode,
+            revenue_split,
+            dispute_period,
+            complimentary_tickets,
+        } => concert.create_concert(
+            name,
+            description,
+            creator,
+            number_of_tickets,
+            date,
+            seat_map,
+            sale_mode,
+            revenue_split,
+            dispute_period,
+            complimentary_tickets,
+        ),
         ConcertAction::Hold => concert.hold_concert().await,
-        ConcertAction::BuyTickets { amount, metadata } => {
-            concert.buy_tickets(amount, metadata).await
+        ConcertAction::IssueComplimentary {
+            to,
+            amount,
+            metadata,
+        } => concert.issue_complimentary(to, amount, metadata).await,
+        ConcertAction::UpdateConcert {
+            name,
+            description,
+            date,
+        } => concert.update_concert(name, description, date),
+        ConcertAction::RequestRescheduleRefund => concert.request_reschedule_refund().await,
+        ConcertAction::Announce { message } => concert.announce(message),
+        ConcertAction::DeliverAnnouncement => concert.deliver_announcement(),
+        ConcertAction::RedeemTicket { ticket_id } => concert.redeem_ticket(ticket_id),
+        ConcertAction::ClaimBadge => concert.claim_badge().await,
+        ConcertAction::IncreaseSupply { amount } => concert.increase_supply(amount),
+        ConcertAction::DecreaseSupply { amount } => concert.decrease_supply(amount),
+        ConcertAction::AssignPartnerQuota { partner, quota } => {
+            concert.assign_partner_quota(partner, quota)
+        }
+        ConcertAction::PartnerSell {
+            to,
+            amount,
+            metadata,
+        } => concert.partner_sell(to, amount, metadata).await,
+        ConcertAction::BuyTickets {
+            amount,
+            metadata,
+            request_id,
+            seats,
+            promo_code,
+            recipient,
+        } => {
+            concert
+                .buy_tickets(amount, metadata, request_id, seats, promo_code, recipient)
+                .await
         }
+        ConcertAction::SetOperator { operator, enabled } => concert.set_operator(operator, enabled),
+        ConcertAction::CommitClaim { commitment } => concert.commit_claim(commitment),
+        ConcertAction::ClaimTickets { secret } => concert.claim_tickets(secret).await,
+        ConcertAction::AddPromoCode {
+            code_hash,
+            discount_percent,
+            max_uses,
+            expires_at,
+        } => concert.add_promo_code(code_hash, discount_percent, max_uses, expires_at),
+        ConcertAction::Withdraw => concert.withdraw(),
+        ConcertAction::RequestRefund { ticket_ids } => concert.request_refund(ticket_ids).await,
+        ConcertAction::CancelConcert => concert.cancel_concert(),
+        ConcertAction::ReserveTickets { amount, seats } => concert.reserve_tickets(amount, seats),
+        ConcertAction::CompletePurchase { metadata } => concert.complete_purchase(metadata).await,
+        ConcertAction::JoinWaitlist { amount } => concert.join_waitlist(amount),
+        ConcertAction::OpenLottery {
+            tickets_per_winner,
+            seed_commitment,
+        } => concert.open_lottery(tickets_per_winner, seed_commitment),
+        ConcertAction::EnterLottery { commitment } => concert.enter_lottery(commitment),
+        ConcertAction::CloseLotteryRegistration => concert.close_lottery_registration(),
+        ConcertAction::RevealLotteryEntry { secret } => concert.reveal_lottery_entry(secret),
+        ConcertAction::DrawLottery { seed } => concert.draw_lottery(seed),
+        ConcertAction::ForceDrawLottery => concert.force_draw_lottery(),
+        ConcertAction::ClaimAuctionRefund => concert.claim_auction_refund(),
+        ConcertAction::StartSealedBidAuction {
+            tickets,
+            seats,
+            commit_deadline,
+            reveal_deadline,
+        } => concert.start_sealed_bid_auction(tickets, seats, commit_deadline, reveal_deadline),
+        ConcertAction::CommitBid { commitment } => concert.commit_bid(commitment),
+        ConcertAction::RevealBid {
+            amount,
+            salt,
+            metadata,
+        } => concert.reveal_bid(amount, salt, metadata),
+        ConcertAction::SettleSealedBidAuction => concert.settle_sealed_bid_auction(),
+        ConcertAction::ClaimAuctionTicket => concert.claim_auction_ticket().await,
+        ConcertAction::RetryPendingOperation => concert.retry_pending_operation().await,
+        ConcertAction::AbortPendingOperation => concert.abort_pending_operation().await,
+        ConcertAction::Pause { reason } => concert.pause(reason),
+        ConcertAction::Unpause => concert.unpause(),
+        ConcertAction::ProposeOwner { new_owner } => concert.propose_owner(new_owner),
+        ConcertAction::AcceptOwnership => concert.accept_ownership(),
+        ConcertAction::SetTokenContract { contract_id } => concert.set_token_contract(contract_id),
+        ConcertAction::SetPlatformFee { fee } => concert.set_platform_fee(fee),
+        ConcertAction::WithdrawFees => concert.withdraw_fees(),
     }
 }
 
@@ -70,9 +286,24 @@ fn reply(payload: impl Encode) -> Result<MessageId> {
     msg::reply(payload, 0)
 }
 
+//La funcion manda valor a una cuenta, por ejemplo para regresar lo que se pago de mas
+fn send_value(to: ActorId, value: u128) {
+    if value > 0 {
+        msg::send_bytes(to, [], value).expect("Error during sending value");
+    }
+}
+
+//Dice si ya termino el plazo para revelar del sorteo
+fn reveal_period_over(lottery: &Lottery) -> bool {
+    lottery
+        .reveal_deadline
+        .map_or(false, |deadline| exec::block_timestamp() > deadline)
+}
+
 //Implementamos la funcionalidad para la estructura Concert
 impl Concert {
     //La funcion en la que creamos un concierto
+    #[allow(clippy::too_many_arguments)]
     fn create_concert(
         &mut self,
         name: String,
@@ -80,20 +311,137 @@ impl Concert {
         creator: ActorId,
         number_of_tickets: u128,
         date: u128,
+        seat_map: Vec<SeatSection>,
+        sale_mode: SaleMode,
+        revenue_split: Vec<(ActorId, u8)>,
+        dispute_period: u64,
+        complimentary_tickets: u128,
     ) {
         //Solo podemos crear un concierto en el smart contract
         if self.running {
             panic!("CONCERT: There is already a concert registered.")
         }
+        //Lo recaudado del concierto anterior que no se ha retirado se guarda para cada beneficiario,
+        //pero solo despues del periodo de disputa, mientras tanto se puede tener que reembolsar
+        let unwithdrawn: Vec<(ActorId, u128)> = self
+            .beneficiaries()
+            .iter()
+            .map(|(beneficiary, _)| (*beneficiary, self.withdrawable(beneficiary)))
+            .filter(|(_, amount)| *amount > 0)
+            .collect();
+        if !unwithdrawn.is_empty() && !self.dispute_period_over() {
+            panic!("CONCERT: The revenue of the previous concert is held until the dispute period ends");
+        }
+        for (beneficiary, amount) in unwithdrawn {
+            *self.carried_revenue.entry(beneficiary).or_default() += amount;
+        }
+        //Los porcentajes deben sumar 100 y cada beneficiario aparece una sola vez
+        if !revenue_split.is_empty() {
+            let total: u32 = revenue_split.iter().map(|(_, share)| *share as u32).sum();
+            let invalid_beneficiary =
+                revenue_split
+                    .iter()
+                    .enumerate()
+                    .any(|(i, (account, share))| {
+                        *account == ZERO_ID
+                            || *share == 0
+                            || revenue_split[..i].iter().any(|(other, _)| other == account)
+                    });
+            if total != 100 || invalid_beneficiary {
+                panic!("CONCERT: Invalid revenue split");
+            }
+        }
+        //Con mapa de asientos cada boleto corresponde a un asiento
+        if !seat_map.is_empty() {
+            let capacity: u128 = seat_map.iter().map(|section| section.capacity()).sum();
+            if capacity != number_of_tickets {
+                panic!("CONCERT: The number of tickets must match the seats in the seat map");
+            }
+            for (i, section) in seat_map.iter().enumerate() {
+                if seat_map[..i].iter().any(|other| other.name == section.name) {
+                    panic!("CONCERT: Section names in the seat map must be unique");
+                }
+            }
+        }
+        //El precio de la subasta solo puede bajar y debe durar algo de tiempo
+        if let SaleMode::DutchAuction {
+            start_price,
+            floor_price,
+            start_time,
+            end_time,
+            ..
+        } = sale_mode
+        {
+            if start_price < floor_price || end_time <= start_time {
+                panic!("CONCERT: Invalid dutch auction parameters");
+            }
+        }
+        //Las fases de precio deben estar ordenadas por su inicio
+        if let SaleMode::Scheduled { phases } = &sale_mode {
+            if phases.is_empty()
+                || phases
+                    .windows(2)
+                    .any(|pair| pair[0].starts_at >= pair[1].starts_at)
+            {
+                panic!("CONCERT: Invalid price schedule");
+            }
+        }
+        //Los depositos de una subasta que no se liquido se regresan
+        //Los boletos ganados en la subasta que no se reclamaron se pierden con el concierto anterior
+        if let Some(auction) = self.sealed_auction.take() {
+            if !auction.settled {
+                for bid in auction.bids {
+                    send_value(bid.bidder, bid.deposit);
+                }
+            }
+        }
+        self.auction_wins.clear();
+        self.reservations.clear();
+        self.reservation_cooldowns.clear();
+        self.waitlist.clear();
+        self.return_lottery_bond();
         self.creator = creator;
         self.concert_id = self.id_counter;
         self.ticket_ft_id = self.concert_id;
+        //issue_purchase suma uno al contador y usa el siguiente id para cada boleto
+        self.first_ticket_id = self.id_counter + 2;
         self.name = name;
         self.description = description;
         self.number_of_tickets = number_of_tickets;
         self.date = date;
         self.running = true;
         self.tickets_left = number_of_tickets;
+        self.seat_map = seat_map;
+        self.sold_seats.clear();
+        self.sale_mode = sale_mode;
+        self.clearing_price = None;
+        self.auction_payments.clear();
+        self.promo_codes.clear();
+        self.proceeds = 0;
+        self.revenue_split = revenue_split;
+        self.withdrawals.clear();
+        self.escrow.clear();
+        self.dispute_period = dispute_period;
+        self.held_at = None;
+        self.cancelled = false;
+        self.complimentary_allocation = complimentary_tickets;
+        self.complimentary_left = complimentary_tickets;
+        self.complimentary_tickets.clear();
+        self.partner_quotas.clear();
+        self.partner_tickets.clear();
+        self.operators.clear();
+        self.operator_sales.clear();
+        self.custodial_claims.clear();
+        self.claim_commitments.clear();
+        self.ticket_payers.clear();
+        self.supply_changes.clear();
+        self.reschedule_refund_deadline = None;
+        self.announcements.clear();
+        self.announcement_queue.clear();
+        self.redeemed_tickets.clear();
+        self.held_tokens = 0;
+        self.ticket_payments.clear();
+        self.returned_tokens = 0;
         //Le respondemos al programa con ConcertEvent que guarda la informacion de la accion hecha
         reply(ConcertEvent::Creation {
             creator,
@@ -105,15 +453,45 @@ impl Concert {
     }
 
     //La funcion con la que podemos comprar boletos
-    async fn buy_tickets(&mut self, amount: u128, mtd: Vec<Option<TokenMetadata>>) {
+    async fn buy_tickets(
+        &mut self,
+        amount: u128,
+        mtd: Vec<Option<TokenMetadata>>,
+        request_id: Option<u128>,
+        seats: Vec<Seat>,
+        promo_code: Option<String>,
+        recipient: Option<TicketRecipient>,
+    ) {
         //Aseguramos que el comprador mande un mensaje desde uns direccion valida
         if msg::source() == ZERO_ID {
             panic!("CONCERT: Message from zero address");
         }
+        //Si la compra es un reenvio de una que ya se hizo respondemos con la compra original
+        if let Some(receipt) = request_id.and_then(|id| self.find_purchase_request(id)) {
+            //El reenvio no compra nada, asi que regresamos el valor que traiga
+            send_value(msg::source(), msg::value());
+            //Si los boletos de la compra original todavia no se crean avisamos que sigue pendiente
+            if let Some(kind) = request_id.and_then(|id| self.pending_purchase(id)) {
+                reply(ConcertEvent::OperationPending {
+                    concert_id: self.concert_id,
+                    kind,
+                })
+                .expect("Error during a replying with ConcertEvent::OperationPending");
+                return;
+            }
+            reply(ConcertEvent::Purchase {
+                concert_id: receipt.concert_id,
+                amount: receipt.amount,
+            })
+            .expect("Error during a replying with ConcertEvent::Purchase");
+            return;
+        }
         //Aseguramos que se compre al menos un boleto
         if amount < 1 {
             panic!("CONCERT: Can not buy less than 1 ticket");
         }
+        //Mientras el sorteo no termine los boletos no se venden al publico
+        self.ensure_no_lottery_in_progress();
         //Aseguramos que se compren la cantidad de boletos disponibles
         if self.tickets_left < amount {
             panic!("CONCERT: Not enough tickets");
@@ -122,172 +500,2410 @@ impl Concert {
         if mtd.len() != amount as usize {
             panic!("CONCERT: Metadata not provided for all the tickets");
         }
-        //Por cada boleto que vemos en la metadata hacemos los siguiente
-        for meta in mtd {
-            //Aumentamos el contador del id de los boletos
-            self.id_counter += 1;
-            //El hasmap de metadata es accesado por el valor de la direccion del comprador
-            //Le agregamos al hasmap la metadata del boleto con su id
-            self.metadata
-                .entry(msg::source())
-                .or_default()
-                .insert(self.id_counter + 1, meta);
+        //Aseguramos que los asientos elegidos se puedan vender
+        self.check_seats(amount, &seats);
+        //Cualquiera puede regalar boletos a otra cuenta
+        //Un operador de taquilla ademas puede comprar para un cliente sin cartera
+        let is_operator = self.operators.contains(&msg::source());
+        if matches!(recipient, Some(TicketRecipient::Custodial { .. })) && !is_operator {
+            panic!("CONCERT: Only box-office operators can buy for customers without a wallet");
         }
-        //Agregamos al comprador a la lista
-        self.buyers.insert(msg::source());
+        let buyer = match recipient {
+            None => msg::source(),
+            Some(recipient) => self.recipient_account(recipient),
+        };
+        let origin = if is_operator && buyer != msg::source() {
+            TicketOrigin::Operator(msg::source())
+        } else {
+            TicketOrigin::Sale
+        };
+        //El codigo de descuento se cuenta como usado una vez por boleto
+        let promo = self.redeem_promo_code(promo_code, amount);
+        let discount_percent = promo.map_or(0, |(_, discount_percent)| discount_percent);
+        //Cobramos los boletos al precio actual
+        let (paid, fee) = self.take_payment(amount, discount_percent);
         //Reducimos la cantidad de boletos disponibles
         self.tickets_left -= amount;
-        //Mandamos un mensaje al smart contract de multitoken para que cree los tokens q
-        //que representan los boletos
-        msg::send_for_reply_as::<_, MTKEvent>(
-            self.contract_id,
-            MyMTKAction::Mint {
-                amount,
-                token_metadata: None,
-            },
-            0,
+        self.update_clearing_price();
+        self.issue_purchase(
+            buyer,
+            mtd,
+            request_id,
+            seats,
+            paid,
+            fee,
+            origin,
+            promo.map(|(code_hash, _)| code_hash),
         )
-        .expect("Error in async message to MTK contract")
-        .await
-        .expect("CONCERT: Error minting concert tokens");
+        .await;
+    }
+
+    //Regresa la cuenta que recibe los boletos de una compra hecha para alguien mas
+    //Para un cliente sin cartera se registra su direccion provisional, cada secreto se usa una sola vez
+    fn recipient_account(&mut self, recipient: TicketRecipient) -> ActorId {
+        match recipient {
+            TicketRecipient::Account(account) => {
+                if account == ZERO_ID {
+                    panic!("CONCERT: Can not issue tickets to the zero address");
+                }
+                account
+            }
+            TicketRecipient::Custodial { claim_hash } => {
+                let account = custodial_account(claim_hash);
+                if self.custodial_claims.contains_key(&account) {
+                    panic!("CONCERT: The claim secret was already used");
+                }
+                self.custodial_claims.insert(account, None);
+                account
+            }
+        }
+    }
+
+    //El creador agrega o quita a un operador de taquilla
+    fn set_operator(&mut self, operator: ActorId, enabled: bool) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can set box-office operators");
+        }
+        if operator == ZERO_ID {
+            panic!("CONCERT: The operator can not be the zero address");
+        }
+        if enabled {
+            self.operators.insert(operator);
+        } else {
+            self.operators.remove(&operator);
+        }
+        reply(ConcertEvent::OperatorSet { operator, enabled })
+            .expect("Error during a replying with ConcertEvent::OperatorSet");
+    }
 
-        reply(ConcertEvent::Purchase {
+    //El cliente guarda el compromiso de su secreto antes de reclamar sus boletos
+    fn commit_claim(&mut self, commitment: [u8; 32]) {
+        if msg::source() == ZERO_ID {
+            panic!("CONCERT: Message from zero address");
+        }
+        self.claim_commitments
+            .entry(commitment)
+            .or_insert_with(exec::block_height);
+        reply(ConcertEvent::ClaimCommitted {
             concert_id: self.concert_id,
-            amount,
         })
-        .expect("Error during a replying with ConcertEvent::Purchase");
+        .expect("Error during a replying with ConcertEvent::ClaimCommitted");
     }
 
-    //La funcion con la que volvemos a los boletos en NFTS
-    // MINT SEVERAL FOR A USER
-    async fn hold_concert(&mut self) {
-        //Verificamos que solo el creador del concierto pueda generar los NFTs
+    //Un cliente reclama los boletos guardados en taquilla con el secreto que le dieron
+    //El concierto guarda sus tokens hasta este momento y aqui se los entrega
+    async fn claim_tickets(&mut self, secret: [u8; 32]) {
+        let account = custodial_account(claim_secret_hash(&secret));
+        match self.custodial_claims.get(&account) {
+            Some(None) => {}
+            Some(Some(_)) => panic!("CONCERT: The tickets were already claimed"),
+            None => panic!("CONCERT: Invalid claim secret"),
+        }
+        //El compromiso debe ser de un bloque anterior, uno hecho despues de ver el secreto no sirve
+        let claim_commitment = commitment(&secret, msg::source());
+        match self.claim_commitments.get(&claim_commitment) {
+            Some(block) if *block < exec::block_height() => {}
+            _ => panic!("CONCERT: The claim was not committed in an earlier block"),
+        }
+        let tickets = self.metadata.remove(&account).unwrap_or_default();
+        let ticket_ids: Vec<u128> = tickets.keys().copied().collect();
+        //Los boletos no se pueden mover mientras se estan creando
+        self.ensure_tickets_not_pending(&ticket_ids);
+        self.custodial_claims.insert(account, Some(msg::source()));
+        self.claim_commitments.remove(&claim_commitment);
+        for id in tickets.keys() {
+            if let Some(owner) = self.ledger_nfts.get_mut(id) {
+                *owner = msg::source();
+            }
+        }
+        self.metadata
+            .entry(msg::source())
+            .or_default()
+            .extend(tickets);
+        if let Some(balance) = self.ledger.remove(&account) {
+            *self.ledger.entry(msg::source()).or_default() += balance;
+        }
+        self.buyers.remove(&account);
+        self.buyers.insert(msg::source());
+        let claim = PendingOperationKind::Claim {
+            claimer: msg::source(),
+            account,
+            ticket_ids,
+        };
+        if let Some(event) = self.start_operation(claim).await {
+            reply(event).expect("Error during a replying with ConcertEvent::TicketsClaimed");
+            self.continue_operations().await;
+        }
+    }
+
+    //El creador da boletos de cortesia, no se cobran y no cuentan en los boletos a la venta
+    async fn issue_complimentary(
+        &mut self,
+        to: ActorId,
+        amount: u128,
+        mtd: Vec<Option<TokenMetadata>>,
+    ) {
         if msg::source() != self.creator {
-            panic!("CONCERT: Only creator can hold a concert");
+            panic!("CONCERT: Only creator can issue complimentary tickets");
         }
-        //Declaramos el vector accounts con los compradores y lo volvemos iterable
-        let accounts: Vec<_> = self.buyers.clone().into_iter().collect();
-        //Declaramos el vector de tokens con el valor de ticket_ft_id repetido el numero de veces de los compradores
-        //Esto se hace para recuperar los saldos de los boletos de los compradores
-        let tokens: Vec<TokenId> = iter::repeat(self.ticket_ft_id)
-            .take(accounts.len())
-            .collect();
-        //Buscamos el numero de boletos que cada comprador tiene
-        let balance_response: MTKEvent = msg::send_for_reply_as(
-            self.contract_id,
-            //Obtenemos el numero de tokens que tiene cada usuario
-            MyMTKAction::BalanceOfBatch {
-                accounts,
-                ids: tokens,
-            },
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if to == ZERO_ID {
+            panic!("CONCERT: Can not issue tickets to the zero address");
+        }
+        if amount < 1 {
+            panic!("CONCERT: Can not issue less than 1 ticket");
+        }
+        if self.complimentary_left < amount {
+            panic!("CONCERT: Not enough complimentary tickets");
+        }
+        if mtd.len() != amount as usize {
+            panic!("CONCERT: Metadata not provided for all the tickets");
+        }
+        self.complimentary_left -= amount;
+        self.issue_purchase(
+            to,
+            mtd,
+            None,
+            Vec::new(),
             0,
+            0,
+            TicketOrigin::Complimentary,
+            None,
         )
-        .expect("Error in async message to MTK contract")
-        .await
-        .expect("CONCERT: Error getting balances from the contract");
-        //Verificamos que los balances(la cantidad de boletos que cada comprador tiene) sea correcto
-        let balances: Vec<BalanceReply> =
-            if let MTKEvent::BalanceOf(balance_response) = balance_response {
-                balance_response
-            } else {
-                Vec::new()
-            };
-        // Por cada balance destruimos los tokens asociados
-        for balance in &balances {
-            msg::send_for_reply_as::<_, MTKEvent>(
-                self.contract_id,
-                MyMTKAction::Burn {
-                    id: balance.id,
-                    amount: balance.amount,
-                },
-                0,
-            )
-            .expect("Error in async message to MTK contract")
-            .await
-            .expect("CONCERT: Error burning balances");
-        }
-        //Creamos NFTs 
-        for actor in &self.buyers {
-            let mut ids = vec![];
-            let mut amounts = vec![];
-            let mut meta = vec![];
-            //Buscamos la metadata de cada comprador
-            let actor_metadata = self.metadata.get(actor);
-            //Si tiene metadata se la asignamos a actor_md
-            if let Some(actor_md) = actor_metadata.cloned() {
-                //Iteramos entre los tokens que tiene el comprador y los metemos en los vectore
-                for (token, token_meta) in actor_md {
-                    ids.push(token);
-                    amounts.push(1);
-                    meta.push(token_meta);
+        .await;
+    }
+
+    //El creador cambia los datos del concierto
+    //Si cambia la fecha se abre un periodo en el que los compradores pueden pedir su reembolso
+    fn update_concert(
+        &mut self,
+        name: Option<String>,
+        description: Option<String>,
+        date: Option<u128>,
+    ) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can update the concert");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if let Some(name) = name {
+            self.name = name;
+        }
+        if let Some(description) = description {
+            self.description = description;
+        }
+        match date {
+            Some(new_date) if new_date != self.date => {
+                let previous_date = self.date;
+                self.date = new_date;
+                let refund_deadline = exec::block_timestamp() + RESCHEDULE_REFUND_PERIOD;
+                self.reschedule_refund_deadline = Some(refund_deadline);
+                reply(ConcertEvent::Rescheduled {
+                    concert_id: self.concert_id,
+                    previous_date,
+                    new_date,
+                    refund_deadline,
+                })
+                .expect("Error during a replying with ConcertEvent::Rescheduled");
+            }
+            _ => {
+                reply(ConcertEvent::ConcertUpdated {
+                    concert_id: self.concert_id,
+                })
+                .expect("Error during a replying with ConcertEvent::ConcertUpdated");
+            }
+        }
+    }
+
+    //Quien tiene boletos los regresa despues de que cambio la fecha y se le reembolsa todo a quien los pago
+    //Los boletos de cortesia y los que ya se usaron no se reembolsan
+    async fn request_reschedule_refund(&mut self) {
+        let window_open = self
+            .reschedule_refund_deadline
+            .map_or(false, |deadline| exec::block_timestamp() <= deadline);
+        if !self.running || !window_open {
+            panic!("CONCERT: There is no reschedule refund window open");
+        }
+        let holder = msg::source();
+        let ticket_ids: Vec<u128> = self
+            .metadata
+            .get(&holder)
+            .map(|tickets| {
+                tickets
+                    .keys()
+                    .filter(|id| {
+                        self.ticket_payments.contains_key(*id)
+                            && !self.redeemed_tickets.contains_key(*id)
+                    })
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.ensure_tickets_not_pending(&ticket_ids);
+        let win = self.auction_wins.remove(&holder);
+        if ticket_ids.is_empty() && win.is_none() {
+            panic!("CONCERT: Nothing to refund");
+        }
+        let mut tickets = ticket_ids.len() as u128;
+        let mut refunded = 0;
+        //El boleto ganado en la subasta que no se ha reclamado tambien se cancela
+        if let Some(win) = win {
+            if let (Some(seat), Some(auction)) = (&win.seat, self.sealed_auction.as_mut()) {
+                auction.seats.retain(|held| held != seat);
+            }
+            if let Some(escrow) = self.escrow.get_mut(&holder) {
+                escrow.paid -= win.paid;
+                escrow.fee -= win.fee;
+                if escrow.paid == 0 {
+                    self.escrow.remove(&holder);
                 }
-                //Convertimos a cada uno de estos tokens en un NFT con la funcion MIntBatch de multitoken
-                //Cuando en amounts utilizas 1, la funcion los vuelve NFTs
-                msg::send_for_reply_as::<_, MTKEvent>(
-                    self.contract_id,
-                    MyMTKAction::MintBatch {
-                        ids,
-                        amounts,
-                        tokens_metadata: meta,
-                    },
-                    0,
-                )
-                .expect("Error in async message to MTK contract")
-                .await
-                .expect("CONCERT: Error minting tickets");
             }
+            self.proceeds -= win.paid - win.fee;
+            self.fee_balance -= win.fee;
+            self.total_fees -= win.fee;
+            self.return_tickets(1);
+            send_value(holder, win.paid);
+            tickets += 1;
+            refunded += win.paid;
         }
-        //Declaramos al concierto como finalizado
-        self.running = false;
-        reply(ConcertEvent::Hold {
+        if !ticket_ids.is_empty() {
+            match self.refund_tickets(holder, ticket_ids).await {
+                Some(amount) => refunded += amount,
+                None => return,
+            }
+        }
+        reply(ConcertEvent::RescheduleRefund {
             concert_id: self.concert_id,
+            amount: refunded,
+            tickets,
         })
-        .expect("Error during a replying with ConcertEvent::Hold");
+        .expect("Error during a replying with ConcertEvent::RescheduleRefund");
+        self.continue_operations().await;
     }
-}
 
-//Esta funcion obtiene la informacion de la variable global CONTRACT y construye 
-//una estructura State que representa el estado actual del concierto
-fn common_state() -> State {
-    //Obtenemos el estado actual de CONTRACT
-    let Concert {
-        owner_id,
-        contract_id,
-        name,
-        description,
-        ticket_ft_id,
-        creator,
-        number_of_tickets,
-        tickets_left,
-        date,
-        buyers,
-        id_counter,
-        concert_id,
-        running,
-        metadata,
-    } = unsafe { CONTRACT.get_or_insert(Default::default()) };
+    //El creador manda un aviso a todos los compradores y se entrega la primera parte
+    fn announce(&mut self, message: String) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can send announcements");
+        }
+        if message.is_empty() || message.len() > MAX_ANNOUNCEMENT_LENGTH {
+            panic!("CONCERT: Invalid announcement length");
+        }
+        if !self.announcement_queue.is_empty() {
+            panic!("CONCERT: The previous announcement is still being delivered");
+        }
+        self.announcement_queue = self.buyers.iter().copied().collect();
+        self.announcements.push(Announcement {
+            message,
+            timestamp: exec::block_timestamp(),
+            recipients: self.announcement_queue.len() as u32,
+            delivered: 0,
+        });
+        self.deliver_announcement_batch();
+    }
 
-    //Creamos una estructura State como la de lib/io con los valores obtenidos de CONTRACT
-    State {
-        owner_id: *owner_id,
-        contract_id: *contract_id,
-        name: name.clone(),
-        description: description.clone(),
-        ticket_ft_id: *ticket_ft_id,
-        creator: *creator,
-        number_of_tickets: *number_of_tickets,
-        tickets_left: *tickets_left,
-        date: *date,
-        //Copiamos cada elemento del vector 
-        buyers: buyers.iter().copied().collect(),
-        id_counter: *id_counter,
-        concert_id: *concert_id,
-        running: *running,
-        //Volvemos el hasmap en un vector
-        metadata: metadata
+    //Entrega el aviso pendiente a la siguiente parte de los compradores
+    //Cualquiera puede continuar la entrega porque el aviso ya lo mando el creador
+    fn deliver_announcement(&mut self) {
+        if self.announcement_queue.is_empty() {
+            panic!("CONCERT: There is no announcement to deliver");
+        }
+        self.deliver_announcement_batch();
+    }
+
+    //Manda el ultimo aviso a lo mas a ANNOUNCEMENT_BATCH_SIZE buzones
+    fn deliver_announcement_batch(&mut self) {
+        let announcement = self
+            .announcements
+            .last_mut()
+            .expect("CONCERT: There is no announcement to deliver");
+        let batch_size = ANNOUNCEMENT_BATCH_SIZE.min(self.announcement_queue.len());
+        for account in self.announcement_queue.drain(..batch_size) {
+            msg::send(
+                account,
+                ConcertEvent::Announcement {
+                    concert_id: self.concert_id,
+                    message: announcement.message.clone(),
+                },
+                0,
+            )
+            .expect("Error during sending ConcertEvent::Announcement");
+        }
+        announcement.delivered += batch_size as u32;
+        reply(ConcertEvent::AnnouncementDelivered {
+            concert_id: self.concert_id,
+            delivered: announcement.delivered,
+            remaining: self.announcement_queue.len() as u32,
+        })
+        .expect("Error during a replying with ConcertEvent::AnnouncementDelivered");
+    }
+
+    //El creador o un operador de taquilla marca un boleto del concierto como usado en la entrada
+    fn redeem_ticket(&mut self, ticket_id: u128) {
+        if msg::source() != self.creator && !self.operators.contains(&msg::source()) {
+            panic!("CONCERT: Only creator or box-office operators can redeem tickets");
+        }
+        if self.cancelled {
+            panic!("CONCERT: The concert was cancelled");
+        }
+        //Los boletos de conciertos anteriores siguen en metadata, los de este concierto empiezan en first_ticket_id
+        if ticket_id < self.first_ticket_id {
+            panic!("CONCERT: The ticket does not belong to this concert");
+        }
+        if self.redeemed_tickets.contains_key(&ticket_id) {
+            panic!("CONCERT: The ticket was already redeemed");
+        }
+        //El boleto puede estar en una compra, entrega o reembolso que todavia no termina
+        self.ensure_tickets_not_pending(&[ticket_id]);
+        let holder = self
+            .metadata
             .iter()
-            .map(|(k, v)| (*k, v.iter().map(|(k, v)| (*k, v.clone())).collect()))
-            .collect(),
+            .find_map(|(holder, tickets)| tickets.contains_key(&ticket_id).then_some(*holder))
+            .expect("CONCERT: The ticket does not exist");
+        //Los boletos guardados en taquilla se tienen que reclamar antes de entrar
+        if self.custodial_claims.get(&holder) == Some(&None) {
+            panic!("CONCERT: The ticket has not been claimed");
+        }
+        self.redeemed_tickets.insert(ticket_id, holder);
+        reply(ConcertEvent::TicketRedeemed {
+            concert_id: self.concert_id,
+            ticket_id,
+            holder,
+        })
+        .expect("Error during a replying with ConcertEvent::TicketRedeemed");
+    }
+
+    //Quien uso un boleto en la entrada reclama su insignia de asistencia, una por concierto
+    //El token de la insignia se crea a nombre del concierto y se queda ahi, asi no se puede transferir
+    async fn claim_badge(&mut self) {
+        let holder = msg::source();
+        if !self
+            .redeemed_tickets
+            .values()
+            .any(|account| *account == holder)
+        {
+            panic!("CONCERT: Only holders of a redeemed ticket can claim a badge");
+        }
+        if self.badges.get(&holder).map_or(false, |badges| {
+            badges
+                .iter()
+                .any(|badge| badge.concert_id == self.concert_id)
+        }) {
+            panic!("CONCERT: The badge was already claimed");
+        }
+        let badge = AttendanceBadge {
+            concert_id: self.concert_id,
+            token_id: BADGE_TOKEN_ID_START - self.badge_counter,
+            name: self.name.clone(),
+            date: self.date,
+        };
+        self.badge_counter += 1;
+        self.badges.entry(holder).or_default().push(badge.clone());
+        let mint = PendingOperationKind::Badge {
+            holder,
+            token_id: badge.token_id,
+        };
+        if let Some(event) = self.start_operation(mint).await {
+            reply(event).expect("Error during a replying with ConcertEvent::BadgeClaimed");
+            self.continue_operations().await;
+        }
+    }
+
+    //Busca la insignia de una cuenta por el id de su token
+    fn badge(&self, holder: ActorId, token_id: u128) -> AttendanceBadge {
+        self.badges
+            .get(&holder)
+            .and_then(|badges| badges.iter().find(|badge| badge.token_id == token_id))
+            .cloned()
+            .expect("CONCERT: The badge does not exist")
+    }
+
+    //El creador abre mas boletos a la venta, se ofrecen primero a la lista de espera
+    fn increase_supply(&mut self, amount: u128) {
+        self.ensure_supply_can_change(amount);
+        let previous_supply = self.number_of_tickets;
+        self.number_of_tickets += amount;
+        self.return_tickets(amount);
+        self.record_supply_change(previous_supply);
+    }
+
+    //El creador quita boletos de la venta, no se pueden quitar boletos vendidos o apartados
+    fn decrease_supply(&mut self, amount: u128) {
+        self.ensure_supply_can_change(amount);
+        if self.tickets_left < amount {
+            panic!("CONCERT: Can not remove tickets that are already sold or held");
+        }
+        let previous_supply = self.number_of_tickets;
+        self.number_of_tickets -= amount;
+        self.tickets_left -= amount;
+        self.update_clearing_price();
+        self.record_supply_change(previous_supply);
+    }
+
+    //Verificamos que el creador pueda cambiar el numero de boletos del concierto activo
+    fn ensure_supply_can_change(&self, amount: u128) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can change the ticket supply");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        //Con mapa de asientos el numero de boletos es el numero de asientos
+        if !self.seat_map.is_empty() {
+            panic!("CONCERT: The ticket supply of a seated concert follows its seat map");
+        }
+        if amount < 1 {
+            panic!("CONCERT: The supply must change by at least 1 ticket");
+        }
+    }
+
+    //Guarda el cambio en el historial y lo avisa con un evento
+    fn record_supply_change(&mut self, previous_supply: u128) {
+        self.supply_changes.push(SupplyChange {
+            previous_supply,
+            new_supply: self.number_of_tickets,
+            timestamp: exec::block_timestamp(),
+        });
+        reply(ConcertEvent::SupplyChanged {
+            concert_id: self.concert_id,
+            previous_supply,
+            new_supply: self.number_of_tickets,
+        })
+        .expect("Error during a replying with ConcertEvent::SupplyChanged");
+    }
+
+    //El creador asigna la cuota total de una agencia, la diferencia con la cuota anterior
+    //se aparta o se regresa a la venta al publico
+    fn assign_partner_quota(&mut self, partner: ActorId, quota: u128) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can assign partner quotas");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if partner == ZERO_ID {
+            panic!("CONCERT: The partner can not be the zero address");
+        }
+        //Con mapa de asientos cada boleto necesita su asiento y las agencias no los eligen
+        if !self.seat_map.is_empty() {
+            panic!("CONCERT: Partner quotas are not available with assigned seating");
+        }
+        let current = self.partner_quotas.entry(partner).or_default();
+        if quota < current.sold {
+            panic!("CONCERT: The quota can not be less than the tickets already sold");
+        }
+        let previous = current.quota;
+        if quota > previous && self.tickets_left < quota - previous {
+            panic!("CONCERT: Not enough tickets");
+        }
+        current.quota = quota;
+        if quota > previous {
+            self.tickets_left -= quota - previous;
+        } else {
+            self.return_tickets(previous - quota);
+        }
+        reply(ConcertEvent::PartnerQuotaAssigned {
+            concert_id: self.concert_id,
+            partner,
+            quota,
+        })
+        .expect("Error during a replying with ConcertEvent::PartnerQuotaAssigned");
+    }
+
+    //Una agencia vende boletos de su cuota, los paga al precio actual y los recibe el cliente
+    async fn partner_sell(&mut self, to: ActorId, amount: u128, mtd: Vec<Option<TokenMetadata>>) {
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if to == ZERO_ID {
+            panic!("CONCERT: Can not issue tickets to the zero address");
+        }
+        if amount < 1 {
+            panic!("CONCERT: Can not buy less than 1 ticket");
+        }
+        let remaining = self
+            .partner_quotas
+            .get(&msg::source())
+            .expect("CONCERT: The sender is not a partner")
+            .remaining();
+        if remaining < amount {
+            panic!("CONCERT: Not enough tickets in the partner quota");
+        }
+        if mtd.len() != amount as usize {
+            panic!("CONCERT: Metadata not provided for all the tickets");
+        }
+        let (paid, fee) = self.take_payment(amount, 0);
+        if let Some(quota) = self.partner_quotas.get_mut(&msg::source()) {
+            quota.sold += amount;
+            quota.revenue += paid;
+        }
+        self.issue_purchase(
+            to,
+            mtd,
+            None,
+            Vec::new(),
+            paid,
+            fee,
+            TicketOrigin::Partner(msg::source()),
+            None,
+        )
+        .await;
+    }
+
+    //Aparta boletos para el comprador hasta que pase RESERVATION_PERIOD
+    //Mientras tanto nadie mas los puede comprar y el comprador los paga con CompletePurchase
+    //Si la reservacion expira sin pagarse la cuenta espera otro RESERVATION_PERIOD para volver a apartar
+    fn reserve_tickets(&mut self, amount: u128, seats: Vec<Seat>) {
+        if msg::source() == ZERO_ID {
+            panic!("CONCERT: Message from zero address");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if amount < 1 {
+            panic!("CONCERT: Can not reserve less than 1 ticket");
+        }
+        if amount > MAX_TICKETS_PER_RESERVATION {
+            panic!("CONCERT: Can not reserve more than {MAX_TICKETS_PER_RESERVATION} tickets");
+        }
+        self.ensure_no_lottery_in_progress();
+        if self.tickets_left < amount {
+            panic!("CONCERT: Not enough tickets");
+        }
+        if self.reservations.contains_key(&msg::source()) {
+            panic!("CONCERT: There is already a reservation for this buyer");
+        }
+        if self.reservation_cooldowns.contains_key(&msg::source()) {
+            panic!("CONCERT: The buyer must wait before reserving again");
+        }
+        self.check_seats(amount, &seats);
+        let expires_at = exec::block_timestamp() + RESERVATION_PERIOD;
+        self.tickets_left -= amount;
+        self.reservation_cooldowns
+            .insert(msg::source(), expires_at + RESERVATION_PERIOD);
+        self.reservations.insert(
+            msg::source(),
+            Reservation {
+                amount,
+                seats,
+                expires_at,
+            },
+        );
+        reply(ConcertEvent::Reserved {
+            concert_id: self.concert_id,
+            amount,
+            expires_at,
+        })
+        .expect("Error during a replying with ConcertEvent::Reserved");
+    }
+
+    //Termina la compra de los boletos apartados por el comprador
+    async fn complete_purchase(&mut self, mtd: Vec<Option<TokenMetadata>>) {
+        let reservation = self
+            .reservations
+            .remove(&msg::source())
+            .expect("CONCERT: There is no active reservation for this buyer");
+        //La reservacion se pago, asi que la cuenta puede volver a apartar
+        self.reservation_cooldowns.remove(&msg::source());
+        if mtd.len() != reservation.amount as usize {
+            panic!("CONCERT: Metadata not provided for all the tickets");
+        }
+        let (paid, fee) = self.take_payment(reservation.amount, 0);
+        self.update_clearing_price();
+        self.issue_purchase(
+            msg::source(),
+            mtd,
+            None,
+            reservation.seats,
+            paid,
+            fee,
+            TicketOrigin::Sale,
+            None,
+        )
+        .await;
+    }
+
+    //El creador registra un codigo de descuento, del codigo solo se conoce su hash
+    fn add_promo_code(
+        &mut self,
+        code_hash: [u8; 32],
+        discount_percent: u8,
+        max_uses: u32,
+        expires_at: u64,
+    ) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can add promo codes");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if discount_percent == 0 || discount_percent > 100 {
+            panic!("CONCERT: The discount must be between 1 and 100 percent");
+        }
+        if max_uses < 1 || expires_at <= exec::block_timestamp() {
+            panic!("CONCERT: Invalid promo code parameters");
+        }
+        if self.promo_codes.contains_key(&code_hash) {
+            panic!("CONCERT: The promo code is already registered");
+        }
+        self.promo_codes.insert(
+            code_hash,
+            PromoCode {
+                discount_percent,
+                max_uses,
+                uses: 0,
+                expires_at,
+            },
+        );
+        reply(ConcertEvent::PromoCodeAdded {
+            concert_id: self.concert_id,
+            code_hash,
+        })
+        .expect("Error during a replying with ConcertEvent::PromoCodeAdded");
+    }
+
+    //Revisa el codigo de descuento contra los hashes registrados y cuenta un uso por boleto
+    //Regresa el hash del codigo con su porcentaje de descuento, None si no se mando codigo
+    fn redeem_promo_code(
+        &mut self,
+        promo_code: Option<String>,
+        amount: u128,
+    ) -> Option<([u8; 32], u8)> {
+        let code_hash = promo_code_hash(self.concert_id, &promo_code?);
+        let promo = self
+            .promo_codes
+            .get_mut(&code_hash)
+            .expect("CONCERT: Invalid promo code");
+        if promo.expires_at <= exec::block_timestamp() {
+            panic!("CONCERT: The promo code has expired");
+        }
+        if (promo.remaining_uses() as u128) < amount {
+            panic!("CONCERT: The promo code does not have enough uses left");
+        }
+        promo.uses += amount as u32;
+        Some((code_hash, promo.discount_percent))
+    }
+
+    //Regresa los usos de un codigo de descuento cuando se deshace la compra de unos boletos
+    fn restore_promo_code(&mut self, code_hash: Option<[u8; 32]>, amount: u128) {
+        if let Some(promo) = code_hash.and_then(|code_hash| self.promo_codes.get_mut(&code_hash)) {
+            promo.uses = promo.uses.saturating_sub(amount as u32);
+        }
+    }
+
+    //Regresa el precio actual de un boleto segun el modo de venta
+    fn ticket_price(&self) -> u128 {
+        let now = exec::block_timestamp();
+        if let SaleMode::DutchAuction { start_time, .. } = self.sale_mode {
+            if now < start_time {
+                panic!("CONCERT: The auction has not started");
+            }
+        }
+        if matches!(self.sale_mode, SaleMode::Scheduled { .. })
+            && self.sale_mode.phase_at(now).is_none()
+        {
+            panic!("CONCERT: The sale has not started");
+        }
+        self.sale_mode.price_at(now)
+    }
+
+    //Cobra los boletos con el valor del mensaje y regresa lo que se pago de mas
+    //Regresa lo que se cobro, que queda guardado en el smart contract, y la comision de la plataforma
+    fn take_payment(&mut self, amount: u128, discount_percent: u8) -> (u128, u128) {
+        let full_cost = self.ticket_price() * amount;
+        let cost = full_cost - full_cost * discount_percent as u128 / 100;
+        if msg::value() < cost {
+            panic!("CONCERT: Not enough value to pay for the tickets");
+        }
+        send_value(msg::source(), msg::value() - cost);
+        //En la subasta con precio uniforme la comision se calcula con el precio minimo,
+        //asi lo que se regresa a los compradores nunca incluye comisiones
+        let fee_base = match self.sale_mode {
+            SaleMode::DutchAuction {
+                floor_price,
+                uniform_price: true,
+                ..
+            } => (floor_price * amount).min(cost),
+            _ => cost,
+        };
+        let fee = self.collect_sale(msg::source(), cost, fee_base, amount);
+        if self.sale_mode.is_uniform_auction() {
+            let payment = self.auction_payments.entry(msg::source()).or_default();
+            payment.tickets += amount;
+            payment.paid += cost;
+        }
+        (cost, fee)
+    }
+
+    //Guarda lo cobrado por una venta en el escrow del comprador separando la comision de la plataforma
+    //Regresa la comision que se cobro
+    fn collect_sale(&mut self, buyer: ActorId, cost: u128, fee_base: u128, tickets: u128) -> u128 {
+        let fee = self
+            .platform_fee
+            .map_or(0, |platform_fee| platform_fee.fee_for(fee_base, tickets));
+        self.fee_balance += fee;
+        self.total_fees += fee;
+        self.proceeds += cost - fee;
+        let escrow = self.escrow.entry(buyer).or_default();
+        escrow.paid += cost;
+        escrow.fee += fee;
+        fee
+    }
+
+    //En una subasta con precio uniforme, la venta del ultimo boleto fija el precio de cierre
+    fn update_clearing_price(&mut self) {
+        if self.sale_mode.is_uniform_auction()
+            && self.tickets_left == 0
+            && self.reservations.is_empty()
+            && self.clearing_price.is_none()
+        {
+            let price = self.sale_mode.price_at(exec::block_timestamp());
+            self.clearing_price = Some(price);
+        }
+    }
+
+    //Regresa el precio de cierre de la subasta si ya se conoce
+    //Si la subasta termino sin vender todos los boletos el precio de cierre es el minimo
+    fn auction_clearing_price(&self) -> Option<u128> {
+        match self.sale_mode {
+            SaleMode::DutchAuction {
+                floor_price,
+                end_time,
+                ..
+            } => self
+                .clearing_price
+                .or((exec::block_timestamp() >= end_time).then_some(floor_price)),
+            _ => None,
+        }
+    }
+
+    //Regresa a un comprador de la subasta con precio uniforme lo que pago por arriba del precio de cierre
+    fn claim_auction_refund(&mut self) {
+        if !self.sale_mode.is_uniform_auction() {
+            panic!("CONCERT: The concert is not sold with a uniform price auction");
+        }
+        let clearing_price = self
+            .auction_clearing_price()
+            .expect("CONCERT: The auction clearing price is not known yet");
+        let payment = self
+            .auction_payments
+            .get_mut(&msg::source())
+            .expect("CONCERT: No auction payments for this buyer");
+        let final_cost = payment.tickets * clearing_price;
+        let refund = payment.paid.saturating_sub(final_cost);
+        if refund == 0 {
+            panic!("CONCERT: Nothing to refund");
+        }
+        payment.paid = final_cost;
+        self.proceeds -= refund;
+        if let Some(escrow) = self.escrow.get_mut(&msg::source()) {
+            escrow.paid -= refund;
+        }
+        send_value(msg::source(), refund);
+        reply(ConcertEvent::AuctionRefund {
+            concert_id: self.concert_id,
+            amount: refund,
+        })
+        .expect("Error during a replying with ConcertEvent::AuctionRefund");
+    }
+
+    //Lo que todavia se puede tener que regresar a los compradores de la subasta con precio uniforme
+    //Si no se conoce el precio de cierre se calcula con el precio minimo
+    fn outstanding_auction_refunds(&self) -> u128 {
+        let floor_price = match self.sale_mode {
+            SaleMode::DutchAuction {
+                floor_price,
+                uniform_price: true,
+                ..
+            } => floor_price,
+            _ => return 0,
+        };
+        let clearing_price = self.auction_clearing_price().unwrap_or(floor_price);
+        self.auction_payments
+            .values()
+            .map(|payment| {
+                payment
+                    .paid
+                    .saturating_sub(payment.tickets * clearing_price)
+            })
+            .sum()
+    }
+
+    //Regresa los beneficiarios de lo recaudado, si no se configuraron todo es para el creador
+    fn beneficiaries(&self) -> Vec<(ActorId, u8)> {
+        if self.revenue_split.is_empty() {
+            vec![(self.creator, 100)]
+        } else {
+            self.revenue_split.clone()
+        }
+    }
+
+    //Calcula lo que un beneficiario puede retirar, sin contar lo que se puede tener que regresar
+    fn withdrawable(&self, beneficiary: &ActorId) -> u128 {
+        let share = self
+            .beneficiaries()
+            .iter()
+            .find(|(account, _)| account == beneficiary)
+            .map_or(0, |(_, share)| *share as u128);
+        let distributable = self
+            .proceeds
+            .saturating_sub(self.outstanding_auction_refunds());
+        let withdrawn = self
+            .withdrawals
+            .get(beneficiary)
+            .copied()
+            .unwrap_or_default();
+        (distributable * share / 100).saturating_sub(withdrawn)
+    }
+
+    //Dice si ya termino el periodo de disputa despues del hold
+    fn dispute_period_over(&self) -> bool {
+        self.held_at.map_or(false, |held_at| {
+            exec::block_timestamp() >= held_at + self.dispute_period
+        })
+    }
+
+    //Un beneficiario retira su parte de lo recaudado junto con lo que no retiro de conciertos anteriores
+    //Lo recaudado se guarda en escrow hasta que pasa el periodo de disputa despues del hold
+    fn withdraw(&mut self) {
+        let carried = self
+            .carried_revenue
+            .get(&msg::source())
+            .copied()
+            .unwrap_or_default();
+        if carried == 0 && !self.dispute_period_over() {
+            panic!("CONCERT: The revenue is held until the dispute period after the hold ends");
+        }
+        let current = if self.dispute_period_over() {
+            self.withdrawable(&msg::source())
+        } else {
+            0
+        };
+        let amount = carried + current;
+        if amount == 0 {
+            panic!("CONCERT: Nothing to withdraw");
+        }
+        self.carried_revenue.remove(&msg::source());
+        *self.withdrawals.entry(msg::source()).or_default() += current;
+        send_value(msg::source(), amount);
+        reply(ConcertEvent::Withdrawn {
+            concert_id: self.concert_id,
+            amount,
+        })
+        .expect("Error during a replying with ConcertEvent::Withdrawn");
+    }
+
+    //Quien tiene unos boletos los regresa durante el periodo de disputa y se le reembolsa a quien los pago
+    async fn request_refund(&mut self, ticket_ids: Vec<u128>) {
+        if self.held_at.is_none() || self.dispute_period_over() {
+            panic!("CONCERT: The dispute period is not open");
+        }
+        self.check_refundable(&ticket_ids);
+        self.ensure_tickets_not_pending(&ticket_ids);
+        let tickets = ticket_ids.len() as u128;
+        if let Some(amount) = self.refund_tickets(msg::source(), ticket_ids).await {
+            reply(ConcertEvent::Refunded {
+                concert_id: self.concert_id,
+                amount,
+                tickets,
+            })
+            .expect("Error during a replying with ConcertEvent::Refunded");
+            self.continue_operations().await;
+        }
+    }
+
+    //Verificamos que quien pide el reembolso tenga los boletos, que se hayan pagado y que no se hayan usado
+    fn check_refundable(&self, ticket_ids: &[u128]) {
+        if ticket_ids.is_empty() {
+            panic!("CONCERT: No tickets to refund");
+        }
+        let tickets = self.metadata.get(&msg::source());
+        for (index, id) in ticket_ids.iter().enumerate() {
+            if ticket_ids[..index].contains(id) {
+                panic!("CONCERT: A ticket is repeated");
+            }
+            if !tickets.map_or(false, |tickets| tickets.contains_key(id)) {
+                panic!("CONCERT: The ticket does not belong to the account");
+            }
+            if !self.ticket_payments.contains_key(id) || self.redeemed_tickets.contains_key(id) {
+                panic!("CONCERT: The ticket can not be refunded");
+            }
+        }
+    }
+
+    //Recupera los tokens de los boletos y le regresa a cada quien lo que pago por ellos
+    //Regresa el total reembolsado, o None si la devolucion de los tokens quedo pendiente o espera su turno
+    async fn refund_tickets(&mut self, holder: ActorId, ticket_ids: Vec<u128>) -> Option<u128> {
+        let refund = PendingOperationKind::Refund { holder, ticket_ids };
+        match self.start_operation(refund).await? {
+            ConcertEvent::Refunded { amount, .. } => Some(amount),
+            _ => None,
+        }
+    }
+
+    //Cancela los boletos que ya se regresaron y reembolsa a quien pago cada uno, incluyendo comisiones
+    //Los boletos de agencias regresan a su cuota y los demas a la venta si el concierto sigue activo
+    fn settle_refund(&mut self, holder: ActorId, ticket_ids: &[u128]) -> u128 {
+        let mut refunded = 0;
+        let mut returned = 0;
+        for id in ticket_ids {
+            let payment = match self.ticket_payments.remove(id) {
+                Some(payment) => payment,
+                None => continue,
+            };
+            let refund = self.ticket_refund(&payment);
+            if let Some(auction) = self.auction_payments.get_mut(&payment.payer) {
+                auction.tickets = auction.tickets.saturating_sub(1);
+                auction.paid = auction.paid.saturating_sub(refund);
+            }
+            if let Some(escrow) = self.escrow.get_mut(&payment.payer) {
+                escrow.paid = escrow.paid.saturating_sub(refund);
+                escrow.fee = escrow.fee.saturating_sub(payment.fee);
+                if escrow.paid == 0 {
+                    self.escrow.remove(&payment.payer);
+                }
+            }
+            self.proceeds -= refund - payment.fee;
+            self.fee_balance -= payment.fee;
+            self.total_fees -= payment.fee;
+            self.restore_promo_code(payment.promo_code, 1);
+            match self.partner_tickets.remove(id) {
+                Some(partner) => {
+                    if let Some(quota) = self.partner_quotas.get_mut(&partner) {
+                        quota.sold -= 1;
+                        quota.revenue = quota.revenue.saturating_sub(refund);
+                    }
+                }
+                None => returned += 1,
+            }
+            self.ticket_payers.remove(id);
+            send_value(payment.payer, refund);
+            refunded += refund;
+        }
+        if let Some(tickets) = self.metadata.get_mut(&holder) {
+            for id in ticket_ids {
+                tickets.remove(id);
+            }
+            if tickets.is_empty() {
+                self.metadata.remove(&holder);
+                self.buyers.remove(&holder);
+            }
+        }
+        self.sold_seats.retain(|_, id| !ticket_ids.contains(id));
+        for sales in self.operator_sales.values_mut() {
+            sales.retain(|sale| !sale.ticket_ids.iter().any(|id| ticket_ids.contains(id)));
+        }
+        if self.running {
+            self.return_tickets(returned);
+        }
+        refunded
+    }
+
+    //Lo que se le regresa a quien pago un boleto
+    //En la subasta con precio uniforme no puede ser mas de lo que todavia tiene pagado por boleto,
+    //porque lo pagado arriba del precio de cierre se pudo haber reembolsado ya
+    fn ticket_refund(&self, payment: &TicketPayment) -> u128 {
+        match self.auction_payments.get(&payment.payer) {
+            Some(auction) if auction.tickets > 0 => {
+                payment.paid.min(auction.paid / auction.tickets)
+            }
+            _ => payment.paid,
+        }
+    }
+
+    //Cancela el concierto y le regresa a cada comprador todo lo que pago, incluyendo comisiones
+    //Tambien se regresan los depositos de la subasta de ofertas selladas
+    fn cancel_concert(&mut self) {
+        if msg::source() != self.creator && msg::source() != self.owner_id {
+            panic!("CONCERT: Only the creator or the owner can cancel the concert");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        self.ensure_no_pending_operation();
+        let mut refunded = 0;
+        for (buyer, escrow) in self.escrow.drain() {
+            self.fee_balance -= escrow.fee;
+            self.total_fees -= escrow.fee;
+            refunded += escrow.paid;
+            send_value(buyer, escrow.paid);
+        }
+        //Los depositos que siguen en la subasta o que se perdieron por no revelar
+        if let Some(auction) = self.sealed_auction.take() {
+            for bid in auction.bids {
+                if !auction.settled || bid.amount.is_none() {
+                    refunded += bid.deposit;
+                    send_value(bid.bidder, bid.deposit);
+                }
+            }
+        }
+        self.proceeds = 0;
+        self.running = false;
+        self.cancelled = true;
+        self.tickets_left = 0;
+        self.complimentary_left = 0;
+        self.reservations.clear();
+        self.waitlist.clear();
+        self.return_lottery_bond();
+        self.auction_payments.clear();
+        self.auction_wins.clear();
+        reply(ConcertEvent::ConcertCancelled {
+            concert_id: self.concert_id,
+            refunded,
+        })
+        .expect("Error during a replying with ConcertEvent::ConcertCancelled");
+    }
+
+    //Libera las reservaciones que ya expiraron, sus boletos vuelven a estar disponibles
+    //Se llama de forma perezosa al inicio de cada mensaje
+    fn release_expired_reservations(&mut self) {
+        let now = exec::block_timestamp();
+        let expired: Vec<ActorId> = self
+            .reservations
+            .iter()
+            .filter(|(_, reservation)| reservation.expires_at <= now)
+            .map(|(buyer, _)| *buyer)
+            .collect();
+        for buyer in expired {
+            if let Some(reservation) = self.reservations.remove(&buyer) {
+                self.return_tickets(reservation.amount);
+            }
+        }
+        self.reservation_cooldowns.retain(|_, until| *until > now);
+    }
+
+    //Regresa boletos a la venta, por ejemplo cuando expira una reservacion
+    //Antes de venderlos al publico se le ofrecen a la lista de espera
+    fn return_tickets(&mut self, amount: u128) {
+        self.tickets_left += amount;
+        self.offer_to_waitlist();
+    }
+
+    //Agrega al comprador a la lista de espera cuando ya no hay suficientes boletos
+    fn join_waitlist(&mut self, amount: u128) {
+        if msg::source() == ZERO_ID {
+            panic!("CONCERT: Message from zero address");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if amount < 1 {
+            panic!("CONCERT: Can not wait for less than 1 ticket");
+        }
+        //Las ofertas de la lista de espera no eligen asientos
+        if !self.seat_map.is_empty() {
+            panic!("CONCERT: The waitlist is not available with assigned seating");
+        }
+        if self.tickets_left >= amount {
+            panic!("CONCERT: There are enough tickets, buy them instead");
+        }
+        if self
+            .waitlist
+            .iter()
+            .any(|(account, _)| *account == msg::source())
+        {
+            panic!("CONCERT: Already in the waitlist");
+        }
+        self.waitlist.push((msg::source(), amount));
+        reply(ConcertEvent::JoinedWaitlist {
+            concert_id: self.concert_id,
+            position: self.waitlist.len() as u32,
+        })
+        .expect("Error during a replying with ConcertEvent::JoinedWaitlist");
+    }
+
+    //Le ofrece los boletos disponibles a las primeras cuentas de la lista de espera
+    //La oferta es una reservacion exclusiva que expira despues de WAITLIST_OFFER_PERIOD
+    //y se le avisa a la cuenta con un mensaje a su buzon
+    fn offer_to_waitlist(&mut self) {
+        while let Some((account, amount)) = self.waitlist.first().copied() {
+            //Se respeta el orden, si la primera cuenta no alcanza boletos nadie mas recibe oferta
+            if amount > self.tickets_left || self.reservations.contains_key(&account) {
+                break;
+            }
+            self.waitlist.remove(0);
+            let expires_at = exec::block_timestamp() + WAITLIST_OFFER_PERIOD;
+            self.tickets_left -= amount;
+            self.reservations.insert(
+                account,
+                Reservation {
+                    amount,
+                    seats: Vec::new(),
+                    expires_at,
+                },
+            );
+            msg::send(
+                account,
+                ConcertEvent::WaitlistOffer {
+                    concert_id: self.concert_id,
+                    amount,
+                    expires_at,
+                },
+                0,
+            )
+            .expect("Error during sending ConcertEvent::WaitlistOffer");
+        }
+    }
+
+    //Registra los boletos de una compra ya validada y los crea en el programa de tokens
+    //Los boletos ya se descontaron de tickets_left
+    #[allow(clippy::too_many_arguments)]
+    async fn issue_purchase(
+        &mut self,
+        buyer: ActorId,
+        mtd: Vec<Option<TokenMetadata>>,
+        request_id: Option<u128>,
+        seats: Vec<Seat>,
+        paid: u128,
+        fee: u128,
+        origin: TicketOrigin,
+        promo_code: Option<[u8; 32]>,
+    ) {
+        let amount = mtd.len() as u128;
+        let mut ticket_ids = Vec::with_capacity(mtd.len());
+        let mut seats = seats.into_iter();
+        //Por cada boleto que vemos en la metadata hacemos los siguiente
+        for meta in mtd {
+            //Aumentamos el contador del id de los boletos
+            self.id_counter += 1;
+            //El hasmap de metadata es accesado por el valor de la direccion del comprador
+            //Le agregamos al hasmap la metadata del boleto con su id
+            self.metadata
+                .entry(buyer)
+                .or_default()
+                .insert(self.id_counter + 1, meta);
+            ticket_ids.push(self.id_counter + 1);
+            //Repartimos lo pagado entre los boletos, el residuo se queda en el primero
+            if paid > 0 {
+                let first = ticket_ids.len() == 1;
+                self.ticket_payments.insert(
+                    self.id_counter + 1,
+                    TicketPayment {
+                        payer: msg::source(),
+                        paid: paid / amount + if first { paid % amount } else { 0 },
+                        fee: fee / amount + if first { fee % amount } else { 0 },
+                        promo_code,
+                    },
+                );
+            }
+            //Solo guardamos quien pago si de verdad pago algo, los boletos de cortesia y de agencias no tienen pagador
+            if buyer != msg::source() && paid > 0 {
+                self.ticket_payers
+                    .insert(self.id_counter + 1, msg::source());
+            }
+            match origin {
+                TicketOrigin::Sale => {}
+                TicketOrigin::Complimentary => {
+                    self.complimentary_tickets.insert(self.id_counter + 1);
+                }
+                TicketOrigin::Partner(partner) => {
+                    self.partner_tickets.insert(self.id_counter + 1, partner);
+                }
+                TicketOrigin::Operator(_) => {}
+            }
+            //Apartamos el asiento del boleto si el concierto tiene mapa de asientos
+            if let Some(seat) = seats.next() {
+                self.sold_seats.insert(seat, self.id_counter + 1);
+            }
+        }
+        //Agregamos al comprador a la lista
+        self.buyers.insert(buyer);
+        if let TicketOrigin::Operator(operator) = origin {
+            self.operator_sales
+                .entry(operator)
+                .or_default()
+                .push(OperatorSale {
+                    recipient: buyer,
+                    ticket_ids: ticket_ids.clone(),
+                    paid,
+                    timestamp: exec::block_timestamp(),
+                });
+        }
+        //Recordamos el id de compra de quien paga para reconocer los reenvios
+        let payer = msg::source();
+        if let Some(id) = request_id {
+            self.purchase_requests.entry(payer).or_default().insert(
+                id,
+                PurchaseReceipt {
+                    concert_id: self.concert_id,
+                    amount,
+                    timestamp: exec::block_timestamp(),
+                },
+            );
+        }
+        //Mandamos un mensaje al programa de tokens para que cree los tokens
+        //que representan los boletos
+        let mint = PendingOperationKind::Mint {
+            buyer,
+            payer,
+            amount,
+            ticket_ids,
+            request_id,
+            paid,
+            fee,
+            promo_code,
+            minted: false,
+        };
+        if let Some(event) = self.start_operation(mint).await {
+            reply(event).expect("Error during a replying with ConcertEvent::Purchase");
+            self.continue_operations().await;
+        }
+    }
+
+    //La funcion con la que volvemos a los boletos en NFTS
+    // MINT SEVERAL FOR A USER
+    async fn hold_concert(&mut self) {
+        //Verificamos que solo el creador del concierto pueda generar los NFTs
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can hold a concert");
+        }
+        if self.cancelled {
+            panic!("CONCERT: The concert was cancelled");
+        }
+        //La conversion empieza cuando no hay otras operaciones y ninguna se agrega hasta que termina
+        self.ensure_no_pending_operation();
+        //Guardamos la conversion en la cola, asi si no se termina se puede continuar despues
+        let hold = PendingOperationKind::Hold {
+            minted: Vec::new(),
+            converted: Vec::new(),
+        };
+        if let Some(event) = self.start_operation(hold).await {
+            reply(event).expect("Error during a replying with ConcertEvent::Hold");
+        }
+    }
+
+    //Verificamos que haya un asiento por boleto, que exista en el mapa, que este libre y que no se repita
+    fn check_seats(&self, amount: u128, seats: &[Seat]) {
+        if self.seat_map.is_empty() {
+            if !seats.is_empty() {
+                panic!("CONCERT: This concert does not have assigned seating");
+            }
+            return;
+        }
+        if seats.len() != amount as usize {
+            panic!("CONCERT: A seat must be selected for every ticket");
+        }
+        for (i, seat) in seats.iter().enumerate() {
+            if !self.seat_map.iter().any(|section| section.contains(seat)) {
+                panic!("CONCERT: The seat does not exist in the seat map");
+            }
+            let held = self
+                .reservations
+                .values()
+                .any(|reservation| reservation.seats.contains(seat))
+                || self
+                    .sealed_auction
+                    .as_ref()
+                    .map_or(false, |auction| auction.seats.contains(seat));
+            if held || self.sold_seats.contains_key(seat) || seats[..i].contains(seat) {
+                panic!("CONCERT: The seat is already booked");
+            }
+        }
+    }
+
+    //El creador aparta boletos para una subasta de ofertas selladas
+    //Las ofertas se mandan hasta commit_deadline y se revelan hasta reveal_deadline
+    fn start_sealed_bid_auction(
+        &mut self,
+        tickets: u128,
+        seats: Vec<Seat>,
+        commit_deadline: u64,
+        reveal_deadline: u64,
+    ) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can start a sealed-bid auction");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        if matches!(&self.sealed_auction, Some(auction) if !auction.settled) {
+            panic!("CONCERT: There is already a sealed-bid auction");
+        }
+        //Los asientos de los ganadores de la subasta anterior siguen en ella hasta que reclaman sus boletos
+        if !self.auction_wins.is_empty() {
+            panic!("CONCERT: The tickets of the previous sealed-bid auction have not been claimed");
+        }
+        if tickets < 1 || self.tickets_left < tickets {
+            panic!("CONCERT: Not enough tickets");
+        }
+        if commit_deadline <= exec::block_timestamp() || reveal_deadline <= commit_deadline {
+            panic!("CONCERT: Invalid sealed-bid auction deadlines");
+        }
+        self.check_seats(tickets, &seats);
+        //Los boletos de la subasta se separan de la venta al publico
+        self.tickets_left -= tickets;
+        self.sealed_auction = Some(SealedAuction {
+            tickets,
+            seats,
+            commit_deadline,
+            reveal_deadline,
+            bids: Vec::new(),
+            settled: false,
+        });
+        reply(ConcertEvent::SealedBidAuctionStarted {
+            concert_id: self.concert_id,
+            tickets,
+        })
+        .expect("Error during a replying with ConcertEvent::SealedBidAuctionStarted");
+    }
+
+    //Regresa la subasta de ofertas selladas que no se ha liquidado
+    fn active_sealed_auction(&mut self) -> &mut SealedAuction {
+        match self.sealed_auction.as_mut() {
+            Some(auction) if !auction.settled => auction,
+            _ => panic!("CONCERT: There is no sealed-bid auction"),
+        }
+    }
+
+    //Manda una oferta sellada, el valor del mensaje es el deposito y debe cubrir la oferta
+    fn commit_bid(&mut self, commitment: [u8; 32]) {
+        if msg::source() == ZERO_ID {
+            panic!("CONCERT: Message from zero address");
+        }
+        if msg::value() == 0 {
+            panic!("CONCERT: A deposit is required to bid");
+        }
+        let concert_id = self.concert_id;
+        let auction = self.active_sealed_auction();
+        if exec::block_timestamp() > auction.commit_deadline {
+            panic!("CONCERT: The bidding period is over");
+        }
+        if auction.bids.iter().any(|bid| bid.bidder == msg::source()) {
+            panic!("CONCERT: Already bid in this auction");
+        }
+        auction.bids.push(SealedBid {
+            bidder: msg::source(),
+            commitment,
+            deposit: msg::value(),
+            amount: None,
+            metadata: None,
+        });
+        reply(ConcertEvent::BidCommitted { concert_id })
+            .expect("Error during a replying with ConcertEvent::BidCommitted");
+    }
+
+    //Revela la oferta, debe coincidir con el compromiso y no puede ser mayor al deposito
+    fn reveal_bid(&mut self, amount: u128, salt: [u8; 32], metadata: Option<TokenMetadata>) {
+        let concert_id = self.concert_id;
+        let auction = self.active_sealed_auction();
+        let now = exec::block_timestamp();
+        if now <= auction.commit_deadline || now > auction.reveal_deadline {
+            panic!("CONCERT: It is not the reveal period");
+        }
+        let bid = auction
+            .bids
+            .iter_mut()
+            .find(|bid| bid.bidder == msg::source())
+            .expect("CONCERT: No bid for this bidder");
+        if bid.amount.is_some() {
+            panic!("CONCERT: The bid was already revealed");
+        }
+        if bid_commitment(amount, &salt, msg::source()) != bid.commitment {
+            panic!("CONCERT: The bid does not match the commitment");
+        }
+        if amount == 0 || amount > bid.deposit {
+            panic!("CONCERT: The bid must be covered by the deposit");
+        }
+        bid.amount = Some(amount);
+        bid.metadata = metadata;
+        reply(ConcertEvent::BidRevealed { concert_id, amount })
+            .expect("Error during a replying with ConcertEvent::BidRevealed");
+    }
+
+    //Liquida la subasta cuando termina el periodo para revelar
+    //Las mejores ofertas ganan y pagan lo que ofrecieron, el resto del deposito se les regresa
+    //Los depositos de las ofertas que no se revelaron se quedan como ganancia del concierto
+    fn settle_sealed_bid_auction(&mut self) {
+        let auction = self.active_sealed_auction();
+        if exec::block_timestamp() <= auction.reveal_deadline {
+            panic!("CONCERT: The reveal period is not over");
+        }
+        auction.settled = true;
+        let tickets = auction.tickets;
+        let mut revealed: Vec<SealedBid> = auction
+            .bids
+            .iter()
+            .filter(|bid| bid.amount.is_some())
+            .cloned()
+            .collect();
+        let forfeited: u128 = auction
+            .bids
+            .iter()
+            .filter(|bid| bid.amount.is_none())
+            .map(|bid| bid.deposit)
+            .sum();
+        //Ordenamos por oferta de mayor a menor, en empate gana quien oferto primero
+        revealed.sort_by(|a, b| b.amount.cmp(&a.amount));
+        let winners_count = (tickets as usize).min(revealed.len());
+        //Los asientos se reparten en orden entre los ganadores y los demas se liberan
+        let mut seats = core::mem::take(&mut auction.seats);
+        seats.truncate(winners_count);
+        auction.seats = seats.clone();
+        let mut seats = seats.into_iter();
+
+        let mut winners = Vec::with_capacity(winners_count);
+        self.proceeds += forfeited;
+        for (rank, bid) in revealed.into_iter().enumerate() {
+            let amount = bid.amount.unwrap_or_default();
+            if rank < winners_count {
+                let fee = self.collect_sale(bid.bidder, amount, amount, 1);
+                send_value(bid.bidder, bid.deposit - amount);
+                self.auction_wins.insert(
+                    bid.bidder,
+                    AuctionWin {
+                        paid: amount,
+                        fee,
+                        seat: seats.next(),
+                        metadata: bid.metadata,
+                    },
+                );
+                winners.push((bid.bidder, amount));
+            } else {
+                send_value(bid.bidder, bid.deposit);
+            }
+        }
+        //Los boletos que no se ganaron regresan a la venta
+        self.return_tickets(tickets - winners_count as u128);
+        reply(ConcertEvent::SealedBidAuctionSettled {
+            concert_id: self.concert_id,
+            winners,
+        })
+        .expect("Error during a replying with ConcertEvent::SealedBidAuctionSettled");
+    }
+
+    //El ganador de la subasta reclama su boleto, que ya esta pagado
+    async fn claim_auction_ticket(&mut self) {
+        let win = self
+            .auction_wins
+            .remove(&msg::source())
+            .expect("CONCERT: No auction ticket to claim");
+        if let (Some(seat), Some(auction)) = (&win.seat, self.sealed_auction.as_mut()) {
+            auction.seats.retain(|held| held != seat);
+        }
+        let seats = win.seat.into_iter().collect();
+        self.issue_purchase(
+            msg::source(),
+            vec![win.metadata],
+            None,
+            seats,
+            win.paid,
+            win.fee,
+            TicketOrigin::Sale,
+            None,
+        )
+        .await;
+    }
+
+    //Verificamos que no se esten repartiendo los boletos por sorteo
+    fn ensure_no_lottery_in_progress(&self) {
+        if matches!(&self.lottery, Some(lottery) if lottery.phase != LotteryPhase::Drawn) {
+            panic!("CONCERT: Tickets are being allocated by lottery");
+        }
+    }
+
+    //Regresa el sorteo en la fase indicada o falla
+    fn lottery_in_phase(&mut self, phase: LotteryPhase) -> &mut Lottery {
+        match self.lottery.as_mut() {
+            Some(lottery) if lottery.phase == phase => lottery,
+            _ => panic!("CONCERT: The lottery is not in the {phase:?} phase"),
+        }
+    }
+
+    //El creador abre el registro al sorteo comprometiendose con una semilla secreta
+    //El valor del mensaje es su garantia, que pierde si no revela la semilla antes del plazo
+    fn open_lottery(&mut self, tickets_per_winner: u128, seed_commitment: [u8; 32]) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can open a lottery");
+        }
+        if !self.running {
+            panic!("CONCERT: There is no concert running");
+        }
+        //Los ganadores reciben una reservacion, que no elige asientos
+        if !self.seat_map.is_empty() {
+            panic!("CONCERT: The lottery is not available with assigned seating");
+        }
+        if tickets_per_winner < 1 || tickets_per_winner > self.tickets_left {
+            panic!("CONCERT: Invalid number of tickets per winner");
+        }
+        if self.lottery.is_some() {
+            panic!("CONCERT: There is already a lottery");
+        }
+        if msg::value() == 0 {
+            panic!("CONCERT: A bond is required to open a lottery");
+        }
+        self.lottery = Some(Lottery {
+            tickets_per_winner,
+            seed_commitment,
+            creator_bond: msg::value(),
+            phase: LotteryPhase::Registration,
+            reveal_deadline: None,
+            entries: Vec::new(),
+            seed: None,
+            winners: Vec::new(),
+        });
+        reply(ConcertEvent::LotteryOpened {
+            concert_id: self.concert_id,
+        })
+        .expect("Error during a replying with ConcertEvent::LotteryOpened");
+    }
+
+    //Registra al participante con el compromiso de su secreto
+    //El creador no participa, asi su semilla no decide si el mismo gana
+    fn enter_lottery(&mut self, commitment: [u8; 32]) {
+        if msg::source() == ZERO_ID {
+            panic!("CONCERT: Message from zero address");
+        }
+        if msg::source() == self.creator {
+            panic!("CONCERT: The creator can not enter the lottery");
+        }
+        let concert_id = self.concert_id;
+        let lottery = self.lottery_in_phase(LotteryPhase::Registration);
+        if lottery
+            .entries
+            .iter()
+            .any(|entry| entry.participant == msg::source())
+        {
+            panic!("CONCERT: Already entered the lottery");
+        }
+        lottery.entries.push(LotteryEntry {
+            participant: msg::source(),
+            commitment,
+            secret: None,
+        });
+        reply(ConcertEvent::LotteryEntered { concert_id })
+            .expect("Error during a replying with ConcertEvent::LotteryEntered");
+    }
+
+    //Cierra el registro, a partir de aqui los participantes revelan sus secretos
+    fn close_lottery_registration(&mut self) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can close the lottery registration");
+        }
+        let concert_id = self.concert_id;
+        let lottery = self.lottery_in_phase(LotteryPhase::Registration);
+        lottery.phase = LotteryPhase::Reveal;
+        lottery.reveal_deadline = Some(exec::block_timestamp() + LOTTERY_REVEAL_PERIOD);
+        reply(ConcertEvent::LotteryRegistrationClosed { concert_id })
+            .expect("Error during a replying with ConcertEvent::LotteryRegistrationClosed");
+    }
+
+    //El participante revela su secreto, debe coincidir con su compromiso
+    fn reveal_lottery_entry(&mut self, secret: [u8; 32]) {
+        let concert_id = self.concert_id;
+        let lottery = self.lottery_in_phase(LotteryPhase::Reveal);
+        if reveal_period_over(lottery) {
+            panic!("CONCERT: The reveal period is over");
+        }
+        let entry = lottery
+            .entries
+            .iter_mut()
+            .find(|entry| entry.participant == msg::source())
+            .expect("CONCERT: Not entered in the lottery");
+        if entry.secret.is_some() {
+            panic!("CONCERT: The secret was already revealed");
+        }
+        if commitment(&secret, msg::source()) != entry.commitment {
+            panic!("CONCERT: The secret does not match the commitment");
+        }
+        entry.secret = Some(secret);
+        reply(ConcertEvent::LotteryEntryRevealed { concert_id })
+            .expect("Error during a replying with ConcertEvent::LotteryEntryRevealed");
+    }
+
+    //El creador revela su semilla y se eligen los ganadores entre los que revelaron su secreto
+    //Cada ganador recibe una reservacion exclusiva por LOTTERY_CLAIM_PERIOD
+    fn draw_lottery(&mut self, seed: [u8; 32]) {
+        if msg::source() != self.creator {
+            panic!("CONCERT: Only creator can draw the lottery");
+        }
+        let creator = self.creator;
+        let lottery = self.lottery_in_phase(LotteryPhase::Reveal);
+        if commitment(&seed, creator) != lottery.seed_commitment {
+            panic!("CONCERT: The seed does not match the commitment");
+        }
+        //Revelo su semilla, asi que recupera su garantia
+        let bond = lottery.creator_bond;
+        self.finish_lottery(seed);
+        send_value(creator, bond);
+    }
+
+    //Despues del plazo para revelar cualquiera hace el sorteo sin la semilla del creador,
+    //asi un creador que no revela no detiene la venta para siempre
+    //Cuando se cierra el plazo el creador ya conoce los secretos revelados, asi que puede calcular
+    //el resultado con su semilla y con FORCED_LOTTERY_SEED y quedarse callado si le conviene el segundo
+    //Esa eleccion entre dos resultados es el sesgo que queda, por eso le cuesta su garantia,
+    //que se reparte entre los participantes que revelaron su secreto
+    //Un participante que revela al final tambien puede elegir entre revelar o no, y pierde su lugar en el sorteo si no revela
+    fn force_draw_lottery(&mut self) {
+        let lottery = self.lottery_in_phase(LotteryPhase::Reveal);
+        if !reveal_period_over(lottery) {
+            panic!("CONCERT: The reveal period is not over");
+        }
+        let bond = lottery.creator_bond;
+        let revealed: Vec<ActorId> = lottery
+            .entries
+            .iter()
+            .filter(|entry| entry.secret.is_some())
+            .map(|entry| entry.participant)
+            .collect();
+        self.finish_lottery(FORCED_LOTTERY_SEED);
+        //Lo que no se puede repartir en partes iguales queda como comision de la plataforma
+        let share = bond / (revealed.len() as u128).max(1);
+        for participant in &revealed {
+            send_value(*participant, share);
+        }
+        self.fee_balance += bond - share * revealed.len() as u128;
+    }
+
+    //Si el sorteo no se hizo, por ejemplo porque se cancelo el concierto, el creador recupera su garantia
+    fn return_lottery_bond(&mut self) {
+        if let Some(lottery) = self.lottery.take() {
+            if lottery.phase != LotteryPhase::Drawn {
+                send_value(self.creator, lottery.creator_bond);
+            }
+        }
+    }
+
+    //Elige a los ganadores entre los que revelaron su secreto y les aparta sus boletos
+    fn finish_lottery(&mut self, seed: [u8; 32]) {
+        let tickets_left = self.tickets_left;
+        let lottery = self.lottery_in_phase(LotteryPhase::Reveal);
+        let final_seed = lottery_seed(&seed, &lottery.entries);
+        let candidates: Vec<ActorId> = lottery
+            .entries
+            .iter()
+            .filter(|entry| entry.secret.is_some())
+            .map(|entry| entry.participant)
+            .collect();
+        let tickets_per_winner = lottery.tickets_per_winner;
+        let winners = draw_winners(
+            &final_seed,
+            candidates,
+            (tickets_left / tickets_per_winner) as usize,
+        );
+        lottery.seed = Some(final_seed);
+        lottery.winners = winners.clone();
+        lottery.phase = LotteryPhase::Drawn;
+
+        let expires_at = exec::block_timestamp() + LOTTERY_CLAIM_PERIOD;
+        for winner in &winners {
+            //Si el ganador ya tenia boletos apartados se suman a los del sorteo
+            let reservation = self.reservations.entry(*winner).or_insert(Reservation {
+                amount: 0,
+                seats: Vec::new(),
+                expires_at,
+            });
+            reservation.amount += tickets_per_winner;
+            reservation.expires_at = expires_at;
+            self.tickets_left -= tickets_per_winner;
+            msg::send(
+                *winner,
+                ConcertEvent::LotteryWon {
+                    concert_id: self.concert_id,
+                    amount: tickets_per_winner,
+                    expires_at,
+                },
+                0,
+            )
+            .expect("Error during sending ConcertEvent::LotteryWon");
+        }
+        reply(ConcertEvent::LotteryDrawn {
+            concert_id: self.concert_id,
+            winners,
+        })
+        .expect("Error during a replying with ConcertEvent::LotteryDrawn");
+    }
+
+    //Busca una compra anterior del comprador con el mismo id de compra
+    //Antes olvidamos los ids de compra que ya expiraron de todas las cuentas, asi el registro no crece sin limite
+    fn find_purchase_request(&mut self, request_id: u128) -> Option<PurchaseReceipt> {
+        let now = exec::block_timestamp();
+        self.purchase_requests.retain(|_, requests| {
+            requests.retain(|_, receipt| receipt.timestamp + REQUEST_ID_TTL > now);
+            !requests.is_empty()
+        });
+        self.purchase_requests
+            .get(&msg::source())?
+            .get(&request_id)
+            .cloned()
+    }
+
+    //Regresa la operacion de la cola si es el minteo de la compra del comprador con ese id de compra
+    fn pending_purchase(&self, request_id: u128) -> Option<PendingOperationKind> {
+        self.pending_operations
+            .iter()
+            .map(|pending| &pending.kind)
+            .find(|kind| {
+                matches!(
+                    kind,
+                    PendingOperationKind::Mint { payer, request_id: Some(id), .. }
+                        if *payer == msg::source() && *id == request_id
+                )
+            })
+            .cloned()
+    }
+
+    //Verificamos que no haya operaciones con el programa de tokens en la cola
+    fn ensure_no_pending_operation(&self) {
+        if !self.pending_operations.is_empty() {
+            panic!("CONCERT: There is a pending operation with the token contract");
+        }
+    }
+
+    //Verificamos que los boletos no esten en una operacion de la cola, mientras tanto no se pueden usar ni mover
+    fn ensure_tickets_not_pending(&self, ticket_ids: &[u128]) {
+        if self.pending_operations.iter().any(|pending| {
+            pending
+                .kind
+                .ticket_ids()
+                .iter()
+                .any(|id| ticket_ids.contains(id))
+        }) {
+            panic!("CONCERT: The tickets are in a pending operation with the token contract");
+        }
+    }
+
+    //Agrega una operacion con el programa de tokens al final de la cola
+    //Las operaciones se hacen una por una porque en multitoken los tokens fungibles de todas las compras son iguales,
+    //y el saldo del concierto solo dice que paso con una operacion si no hay otra a medias
+    //Regresa el id de la operacion y si es la primera de la cola, en ese caso la hace este mensaje
+    fn queue_operation(&mut self, kind: PendingOperationKind) -> (u64, bool) {
+        //La conversion de los boletos es la ultima operacion del concierto
+        if self
+            .pending_operations
+            .iter()
+            .any(|pending| matches!(pending.kind, PendingOperationKind::Hold { .. }))
+        {
+            panic!("CONCERT: The tickets are being converted");
+        }
+        self.operation_counter += 1;
+        let first = self.pending_operations.is_empty();
+        self.pending_operations.push(PendingOperation {
+            id: self.operation_counter,
+            message_id: msg::id(),
+            account: msg::source(),
+            kind,
+            interrupted: !first,
+        });
+        (self.operation_counter, first)
+    }
+
+    //Guarda la operacion en la cola antes de esperar la respuesta del programa de tokens,
+    //asi queda registrada aunque el mensaje se quede sin gas
+    //Si es la primera de la cola la hace y regresa el evento con su resultado,
+    //si no responde que la operacion quedo pendiente y regresa None
+    async fn start_operation(&mut self, kind: PendingOperationKind) -> Option<ConcertEvent> {
+        let (id, first) = self.queue_operation(kind.clone());
+        if !first || !self.run_operation(&kind, false).await {
+            self.reply_operation_pending(id);
+            return None;
+        }
+        Some(self.complete_operation(id).1)
+    }
+
+    //Despues de completar su operacion el mensaje sigue con las que esperan su turno en la cola,
+    //a lo mas QUEUED_OPERATIONS_PER_MESSAGE para no gastar mucho gas de quien lo mando
+    //El resultado de cada una se manda al buzon de quien la pidio
+    async fn continue_operations(&mut self) {
+        for _ in 0..QUEUED_OPERATIONS_PER_MESSAGE {
+            let (id, kind) = match self.take_idle_operation() {
+                Some(operation) => operation,
+                None => return,
+            };
+            if !self.run_operation(&kind, true).await {
+                self.interrupt_operation(id);
+                return;
+            }
+            let (account, event) = self.complete_operation(id);
+            msg::send(account, event, 0).expect("Error during sending the result of an operation");
+        }
+    }
+
+    //Toma la primera operacion de la cola para este mensaje si ningun otro la esta haciendo,
+    //asi no hay dos mensajes esperando al programa de tokens por la misma operacion
+    fn take_idle_operation(&mut self) -> Option<(u64, PendingOperationKind)> {
+        let pending = self
+            .pending_operations
+            .first_mut()
+            .filter(|pending| pending.interrupted)?;
+        pending.message_id = msg::id();
+        pending.interrupted = false;
+        Some((pending.id, pending.kind.clone()))
+    }
+
+    //Quita una operacion de la cola
+    fn remove_operation(&mut self, id: u64) -> PendingOperation {
+        let position = self
+            .pending_operations
+            .iter()
+            .position(|pending| pending.id == id)
+            .expect("CONCERT: The operation is not pending");
+        self.pending_operations.remove(position)
+    }
+
+    //Quita de la cola la operacion que se completo y hace lo que faltaba despues de la respuesta del programa de tokens
+    //Regresa la cuenta que pidio la operacion y el evento con su resultado
+    fn complete_operation(&mut self, id: u64) -> (ActorId, ConcertEvent) {
+        let pending = self.remove_operation(id);
+        let concert_id = self.concert_id;
+        let event = match pending.kind {
+            PendingOperationKind::Mint { amount, .. } => {
+                ConcertEvent::Purchase { concert_id, amount }
+            }
+            PendingOperationKind::Badge { holder, token_id } => ConcertEvent::BadgeClaimed {
+                concert_id: self.badge(holder, token_id).concert_id,
+                token_id,
+            },
+            PendingOperationKind::Claim { ticket_ids, .. } => ConcertEvent::TicketsClaimed {
+                concert_id,
+                amount: ticket_ids.len() as u128,
+            },
+            PendingOperationKind::Refund { holder, ticket_ids } => ConcertEvent::Refunded {
+                concert_id,
+                amount: self.settle_refund(holder, &ticket_ids),
+                tickets: ticket_ids.len() as u128,
+            },
+            //Declaramos al concierto como finalizado despues de convertir los boletos
+            PendingOperationKind::Hold { .. } => {
+                self.running = false;
+                self.held_at = Some(exec::block_timestamp());
+                ConcertEvent::Hold { concert_id }
+            }
+        };
+        (pending.account, event)
+    }
+
+    //Marca la operacion como interrumpida, asi queda lista para que cualquiera la continue
+    fn interrupt_operation(&mut self, id: u64) {
+        if let Some(pending) = self
+            .pending_operations
+            .iter_mut()
+            .find(|pending| pending.id == id)
+        {
+            pending.interrupted = true;
+        }
+    }
+
+    //Responde que la operacion con el programa de tokens quedo pendiente o que espera su turno en la cola
+    //El mensaje termina aqui, asi que la operacion queda lista para que cualquiera la continue
+    fn reply_operation_pending(&mut self, id: u64) {
+        self.interrupt_operation(id);
+        let kind = self
+            .pending_operations
+            .iter()
+            .find(|pending| pending.id == id)
+            .map(|pending| pending.kind.clone())
+            .expect("CONCERT: There is no pending operation");
+        reply(ConcertEvent::OperationPending {
+            concert_id: self.concert_id,
+            kind,
+        })
+        .expect("Error during a replying with ConcertEvent::OperationPending");
+    }
+
+    //Continua la primera operacion de la cola, si vuelve a fallar se queda pendiente
+    //Cualquier cuenta puede continuarla, antes de repetir un paso se revisa en el programa de tokens
+    //si ya se habia hecho, asi una respuesta que llego tarde no crea tokens dos veces
+    //Despues sigue con las operaciones que esperan su turno
+    async fn retry_pending_operation(&mut self) {
+        if self.pending_operations.is_empty() {
+            panic!("CONCERT: There is no pending operation");
+        }
+        if self.pause_reason.is_some() && msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can retry operations while the contract is paused");
+        }
+        let (id, kind) = self
+            .take_idle_operation()
+            .expect("CONCERT: The pending operation is still in progress");
+        if !self.run_operation(&kind, true).await {
+            self.reply_operation_pending(id);
+            return;
+        }
+        let (account, event) = self.complete_operation(id);
+        msg::send(account, event, 0).expect("Error during sending the result of an operation");
+        reply(ConcertEvent::OperationResolved {
+            concert_id: self.concert_id,
+        })
+        .expect("Error during a replying with ConcertEvent::OperationResolved");
+        self.continue_operations().await;
+    }
+
+    //Descarta la primera operacion de la cola cuando ningun mensaje la esta haciendo
+    //Si era una compra se deshace y sus NFTs se destruyen, si era una conversion los NFTs ya creados se quedan y el concierto sigue activo
+    //Si era una insignia se borra y la cuenta la puede volver a reclamar, si su token ya se habia creado se queda en el concierto
+    //Si era un reclamo los boletos regresan a la direccion provisional y el secreto se puede volver a usar
+    //Si era un reembolso los tokens que ya se habian recuperado se le regresan a quien tiene los boletos
+    async fn abort_pending_operation(&mut self) {
+        if msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can manage pending operations");
+        }
+        //Mientras se abortan los pasos ya hechos nadie mas puede continuar la operacion
+        let (id, kind) = match self.take_idle_operation() {
+            Some(operation) => operation,
+            None if self.pending_operations.is_empty() => {
+                panic!("CONCERT: There is no pending operation")
+            }
+            None => panic!("CONCERT: The pending operation is still in progress"),
+        };
+        if let PendingOperationKind::Mint {
+            buyer,
+            payer,
+            amount,
+            ticket_ids,
+            request_id,
+            paid,
+            fee,
+            promo_code,
+            minted,
+        } = kind
+        {
+            //Destruimos los tokens que ya se habian creado para la compra,
+            //los que no se pueden destruir quedan registrados
+            let unrevoked = self.revoke_tickets(&ticket_ids, minted).await;
+            self.orphaned_tickets.extend(unrevoked);
+            //Le regresamos al comprador lo que pago, incluyendo la comision de la plataforma
+            self.proceeds -= paid - fee;
+            self.fee_balance -= fee;
+            self.total_fees -= fee;
+            if let Some(escrow) = self.escrow.get_mut(&payer) {
+                escrow.paid -= paid;
+                escrow.fee -= fee;
+            }
+            if let Some(payment) = self.auction_payments.get_mut(&payer) {
+                payment.tickets = payment.tickets.saturating_sub(amount);
+                payment.paid = payment.paid.saturating_sub(paid);
+            }
+            send_value(payer, paid);
+            //Los boletos que no se compraron no gastan los usos del codigo de descuento
+            self.restore_promo_code(promo_code, amount);
+            if let Some(tickets) = self.metadata.get_mut(&buyer) {
+                for id in &ticket_ids {
+                    tickets.remove(id);
+                }
+                if tickets.is_empty() {
+                    self.metadata.remove(&buyer);
+                    self.buyers.remove(&buyer);
+                    //Si eran los boletos de un cliente sin cartera su secreto ya no reclama nada
+                    if self.custodial_claims.get(&buyer) == Some(&None) {
+                        self.custodial_claims.remove(&buyer);
+                    }
+                }
+            }
+            for id in &ticket_ids {
+                self.ticket_payers.remove(id);
+                self.ticket_payments.remove(id);
+                self.redeemed_tickets.remove(id);
+            }
+            for sales in self.operator_sales.values_mut() {
+                sales.retain(|sale| sale.ticket_ids != ticket_ids);
+            }
+            //Los boletos de cortesia y los de agencias regresan a su propia reserva
+            let partner = ticket_ids
+                .first()
+                .and_then(|id| self.partner_tickets.get(id))
+                .copied();
+            if ticket_ids
+                .iter()
+                .any(|id| self.complimentary_tickets.contains(id))
+            {
+                for id in &ticket_ids {
+                    self.complimentary_tickets.remove(id);
+                }
+                self.complimentary_left += amount;
+            } else if let Some(partner) = partner {
+                for id in &ticket_ids {
+                    self.partner_tickets.remove(id);
+                }
+                if let Some(quota) = self.partner_quotas.get_mut(&partner) {
+                    quota.sold -= amount;
+                    quota.revenue -= paid;
+                }
+            } else {
+                self.return_tickets(amount);
+            }
+            self.sold_seats.retain(|_, id| !ticket_ids.contains(id));
+            //La compra ya no existe, asi que su id de compra se puede volver a usar
+            if let (Some(id), Some(requests)) = (request_id, self.purchase_requests.get_mut(&payer))
+            {
+                requests.remove(&id);
+            }
+        } else if let PendingOperationKind::Claim {
+            claimer,
+            account,
+            ticket_ids,
+        } = kind
+        {
+            let mut tickets = HashMap::new();
+            if let Some(claimed) = self.metadata.get_mut(&claimer) {
+                for id in &ticket_ids {
+                    if let Some(meta) = claimed.remove(id) {
+                        tickets.insert(*id, meta);
+                    }
+                }
+                if claimed.is_empty() {
+                    self.metadata.remove(&claimer);
+                    self.buyers.remove(&claimer);
+                }
+            }
+            for id in &ticket_ids {
+                if let Some(owner) = self.ledger_nfts.get_mut(id) {
+                    *owner = account;
+                }
+            }
+            if let Some(balance) = self.ledger.remove(&claimer) {
+                //El saldo del cliente se junto con el que ya tenia, solo regresamos el de taquilla
+                let returned = balance.min(ticket_ids.len() as u128);
+                if balance > returned {
+                    self.ledger.insert(claimer, balance - returned);
+                }
+                self.ledger.insert(account, returned);
+            }
+            self.metadata.insert(account, tickets);
+            self.buyers.insert(account);
+            self.custodial_claims.insert(account, None);
+        } else if let PendingOperationKind::Refund { holder, ticket_ids } = kind {
+            self.return_reclaimed_tickets(holder, &ticket_ids).await;
+        } else if let PendingOperationKind::Badge { holder, token_id } = kind {
+            if let Some(badges) = self.badges.get_mut(&holder) {
+                badges.retain(|badge| badge.token_id != token_id);
+                if badges.is_empty() {
+                    self.badges.remove(&holder);
+                }
+            }
+        }
+        //A quien pidio la operacion se le avisa que ya no se hara
+        let account = self.remove_operation(id).account;
+        if account != msg::source() {
+            msg::send(
+                account,
+                ConcertEvent::OperationAborted {
+                    concert_id: self.concert_id,
+                },
+                0,
+            )
+            .expect("Error during sending ConcertEvent::OperationAborted");
+        }
+        reply(ConcertEvent::OperationAborted {
+            concert_id: self.concert_id,
+        })
+        .expect("Error during a replying with ConcertEvent::OperationAborted");
+    }
+
+    //Pausa el smart contract guardando la razon
+    fn pause(&mut self, reason: String) {
+        if msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can pause the contract");
+        }
+        self.pause_reason = Some(reason.clone());
+        reply(ConcertEvent::Paused { reason })
+            .expect("Error during a replying with ConcertEvent::Paused");
+    }
+
+    //Quita la pausa del smart contract
+    fn unpause(&mut self) {
+        if msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can unpause the contract");
+        }
+        if self.pause_reason.take().is_none() {
+            panic!("CONCERT: The contract is not paused");
+        }
+        reply(ConcertEvent::Unpaused).expect("Error during a replying with ConcertEvent::Unpaused");
+    }
+
+    //El dueño propone a su sucesor, el cambio solo ocurre cuando el sucesor acepta
+    fn propose_owner(&mut self, new_owner: ActorId) {
+        if msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can propose a new owner");
+        }
+        if new_owner == ZERO_ID {
+            panic!("CONCERT: The new owner can not be the zero address");
+        }
+        self.pending_owner = Some(new_owner);
+        reply(ConcertEvent::OwnerProposed { new_owner })
+            .expect("Error during a replying with ConcertEvent::OwnerProposed");
+    }
+
+    //La direccion propuesta acepta y se vuelve el dueño del smart contract
+    fn accept_ownership(&mut self) {
+        if self.pending_owner != Some(msg::source()) {
+            panic!("CONCERT: Only the proposed owner can accept the ownership");
+        }
+        let previous_owner = self.owner_id;
+        self.owner_id = msg::source();
+        self.pending_owner = None;
+        reply(ConcertEvent::OwnershipTransferred {
+            previous_owner,
+            new_owner: self.owner_id,
+        })
+        .expect("Error during a replying with ConcertEvent::OwnershipTransferred");
+    }
+
+    //Cambia el programa de tokens donde se crean los boletos
+    //Los tokens de un concierto activo viven en el contrato actual, por eso solo se permite
+    //cuando no hay boletos vendidos que falten de convertir en NFTs
+    fn set_token_contract(&mut self, contract_id: ActorId) {
+        if msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can change the token contract");
+        }
+        if self.backend == TokenBackend::Internal {
+            panic!("CONCERT: The internal ledger does not use a token contract");
+        }
+        if contract_id == ZERO_ID {
+            panic!("CONCERT: The token contract can not be the zero address");
+        }
+        self.ensure_no_pending_operation();
+        let sold = self.tickets_left < self.number_of_tickets
+            || self.complimentary_left < self.complimentary_allocation;
+        if self.running && sold {
+            panic!("CONCERT: There are outstanding tickets in the current token contract");
+        }
+        //Los reembolsos recuperan los tokens de los boletos en el contrato actual
+        let reschedule_window_open = self
+            .reschedule_refund_deadline
+            .map_or(false, |deadline| exec::block_timestamp() <= deadline);
+        if (self.held_at.is_some() && !self.dispute_period_over()) || reschedule_window_open {
+            panic!("CONCERT: There are refundable tickets in the current token contract");
+        }
+        //Los boletos de taquilla y los de la subasta que no se han reclamado se entregan en el contrato actual
+        if self.held_tokens > 0
+            || self.custodial_claims.values().any(Option::is_none)
+            || !self.auction_wins.is_empty()
+        {
+            panic!("CONCERT: There are unclaimed tickets in the current token contract");
+        }
+        let previous_contract = self.contract_id;
+        self.contract_id = contract_id;
+        reply(ConcertEvent::TokenContractChanged {
+            previous_contract,
+            new_contract: contract_id,
+        })
+        .expect("Error during a replying with ConcertEvent::TokenContractChanged");
+    }
+
+    //El dueño cambia la comision de la plataforma, solo aplica a las ventas que se hagan despues
+    fn set_platform_fee(&mut self, fee: Option<PlatformFee>) {
+        if msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can set the platform fee");
+        }
+        if matches!(fee, Some(PlatformFee::Percentage(percent)) if percent > 100) {
+            panic!("CONCERT: The platform fee can not be more than 100 percent");
+        }
+        self.platform_fee = fee;
+        reply(ConcertEvent::PlatformFeeChanged { fee })
+            .expect("Error during a replying with ConcertEvent::PlatformFeeChanged");
+    }
+
+    //El dueño retira las comisiones cobradas
+    //Mientras el concierto esta activo una compra se puede deshacer y su comision se regresa
+    fn withdraw_fees(&mut self) {
+        if msg::source() != self.owner_id {
+            panic!("CONCERT: Only the owner can withdraw the fees");
+        }
+        if self.running || !self.pending_operations.is_empty() {
+            panic!("CONCERT: The fees are held until the concert is held");
+        }
+        //Las comisiones se regresan con los reembolsos del periodo de disputa, asi que se esperan a que termine
+        if !self.cancelled && !self.dispute_period_over() {
+            panic!("CONCERT: The fees are held until the dispute period ends");
+        }
+        let amount = core::mem::take(&mut self.fee_balance);
+        if amount == 0 {
+            panic!("CONCERT: Nothing to withdraw");
+        }
+        send_value(msg::source(), amount);
+        reply(ConcertEvent::FeesWithdrawn { amount })
+            .expect("Error during a replying with ConcertEvent::FeesWithdrawn");
+    }
+}
+
+//Esta funcion obtiene la informacion de la variable global CONTRACT y construye 
+//una estructura State que representa el estado actual del concierto
+fn common_state() -> State {
+    //Obtenemos el estado actual de CONTRACT
+    let Concert {
+        owner_id,
+        backend,
+        contract_id,
+        name,
+        description,
+        ticket_ft_id,
+        creator,
+        number_of_tickets,
+        tickets_left,
+        date,
+        buyers,
+        id_counter,
+        concert_id,
+        running,
+        metadata,
+        pending_operations,
+        operation_counter,
+        purchase_requests,
+        pause_reason,
+        pending_owner,
+        ledger,
+        ledger_nfts,
+        seat_map,
+        sold_seats,
+        reservations,
+        reservation_cooldowns,
+        waitlist,
+        lottery,
+        sale_mode,
+        proceeds,
+        clearing_price,
+        auction_payments,
+        sealed_auction,
+        auction_wins,
+        promo_codes,
+        revenue_split,
+        withdrawals,
+        carried_revenue,
+        platform_fee,
+        fee_balance,
+        total_fees,
+        escrow,
+        dispute_period,
+        held_at,
+        cancelled,
+        complimentary_allocation,
+        complimentary_left,
+        complimentary_tickets,
+        partner_quotas,
+        partner_tickets,
+        operators,
+        operator_sales,
+        custodial_claims,
+        claim_commitments,
+        ticket_payers,
+        supply_changes,
+        reschedule_refund_deadline,
+        announcements,
+        announcement_queue,
+        redeemed_tickets,
+        first_ticket_id,
+        badges,
+        badge_counter,
+        nft_tokens,
+        orphaned_tickets,
+        held_tokens,
+        ticket_payments,
+        returned_tokens,
+    } = unsafe { CONTRACT.get_or_insert(Default::default()) };
+
+    //Creamos una estructura State como la de lib/io con los valores obtenidos de CONTRACT
+    State {
+        owner_id: *owner_id,
+        backend: *backend,
+        contract_id: *contract_id,
+        name: name.clone(),
+        description: description.clone(),
+        ticket_ft_id: *ticket_ft_id,
+        creator: *creator,
+        number_of_tickets: *number_of_tickets,
+        tickets_left: *tickets_left,
+        date: *date,
+        //Copiamos cada elemento del vector 
+        buyers: buyers.iter().copied().collect(),
+        id_counter: *id_counter,
+        concert_id: *concert_id,
+        running: *running,
+        //Volvemos el hasmap en un vector
+        metadata: metadata
+            .iter()
+            .map(|(k, v)| (*k, v.iter().map(|(k, v)| (*k, v.clone())).collect()))
+            .collect(),
+        pending_operations: pending_operations.clone(),
+        operation_counter: *operation_counter,
+        purchase_requests: purchase_requests
+            .iter()
+            .map(|(k, v)| (*k, v.iter().map(|(k, v)| (*k, v.clone())).collect()))
+            .collect(),
+        pause_reason: pause_reason.clone(),
+        pending_owner: *pending_owner,
+        ledger: ledger.iter().map(|(k, v)| (*k, *v)).collect(),
+        ledger_nfts: ledger_nfts.iter().map(|(k, v)| (*k, *v)).collect(),
+        seat_map: seat_map.clone(),
+        sold_seats: sold_seats.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+        reservations: reservations.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        reservation_cooldowns: reservation_cooldowns
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect(),
+        waitlist: waitlist.clone(),
+        lottery: lottery.clone(),
+        sale_mode: sale_mode.clone(),
+        proceeds: *proceeds,
+        clearing_price: *clearing_price,
+        auction_payments: auction_payments
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect(),
+        sealed_auction: sealed_auction.clone(),
+        auction_wins: auction_wins.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        promo_codes: promo_codes.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        //La fase de precio depende del momento en el que se lee el estado
+        price_phase: sale_mode.phase_at(exec::block_timestamp()).cloned(),
+        revenue_split: revenue_split.clone(),
+        withdrawals: withdrawals.iter().map(|(k, v)| (*k, *v)).collect(),
+        carried_revenue: carried_revenue.iter().map(|(k, v)| (*k, *v)).collect(),
+        platform_fee: *platform_fee,
+        fee_balance: *fee_balance,
+        total_fees: *total_fees,
+        escrow: escrow.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        dispute_period: *dispute_period,
+        held_at: *held_at,
+        cancelled: *cancelled,
+        complimentary_allocation: *complimentary_allocation,
+        complimentary_left: *complimentary_left,
+        complimentary_tickets: complimentary_tickets.iter().copied().collect(),
+        partner_quotas: partner_quotas
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect(),
+        partner_tickets: partner_tickets.iter().map(|(k, v)| (*k, *v)).collect(),
+        operators: operators.iter().copied().collect(),
+        operator_sales: operator_sales
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect(),
+        custodial_claims: custodial_claims.iter().map(|(k, v)| (*k, *v)).collect(),
+        claim_commitments: claim_commitments.iter().map(|(k, v)| (*k, *v)).collect(),
+        ticket_payers: ticket_payers.iter().map(|(k, v)| (*k, *v)).collect(),
+        supply_changes: supply_changes.clone(),
+        reschedule_refund_deadline: *reschedule_refund_deadline,
+        announcements: announcements.clone(),
+        announcement_queue: announcement_queue.clone(),
+        redeemed_tickets: redeemed_tickets.iter().map(|(k, v)| (*k, *v)).collect(),
+        first_ticket_id: *first_ticket_id,
+        badges: badges.iter().map(|(k, v)| (*k, v.clone())).collect(),
+        badge_counter: *badge_counter,
+        nft_tokens: nft_tokens.iter().map(|(k, v)| (*k, *v)).collect(),
+        orphaned_tickets: orphaned_tickets.clone(),
+        held_tokens: *held_tokens,
+        ticket_payments: ticket_payments
+            .iter()
+            .map(|(k, v)| (*k, v.clone()))
+            .collect(),
+        returned_tokens: *returned_tokens,
+    }
+}
+
+//Esta funcion se ejecuta cuando un mensaje del smart contract se queda sin gas o agota su tiempo de espera
+//Marcamos la operacion que hacia ese mensaje como interrumpida para que cualquiera la continue
+#[no_mangle]
+extern "C" fn handle_signal() {
+    let concert: &mut Concert = unsafe { CONTRACT.get_or_insert(Default::default()) };
+    let message_id = msg::signal_from().expect("Unable to get the message of the signal");
+    if let Some(pending) = concert
+        .pending_operations
+        .iter_mut()
+        .find(|pending| pending.message_id == message_id && !pending.interrupted)
+    {
+        pending.interrupted = true;
     }
 }
 
@@ -0,0 +1,651 @@
+//Este modulo es la abstraccion sobre el programa donde se crean los boletos
+//El smart contract solo pide crear, entregar o convertir los boletos, y aqui se decide que hacer
+//segun el backend elegido en InitConcert
+
+use super::Concert;
+use gear_lib::multitoken::io::*;
+use gear_lib::non_fungible_token::token::TokenMetadata as NftMetadata;
+use gstd::{exec, msg, prelude::*, ActorId};
+use multitoken_io::MyMTKAction;
+use nft_io::{NFTAction, NFTEvent};
+use venta_boletos_io::*;
+
+//El numero maximo de bloques que esperamos la respuesta del programa de tokens
+const TOKEN_REPLY_TIMEOUT: u32 = 20;
+//Los bits altos del id de transaccion del programa de NFTs dicen que paso es,
+//los de en medio son el id de la operacion de la cola y los bajos el id del boleto
+//Asi un reintento repite el id y recibe la respuesta guardada, pero una operacion nueva
+//sobre los mismos boletos, por ejemplo despues de abortar, nunca recibe una respuesta vieja
+const NFT_TRANSACTION_SHIFT: u32 = 60;
+const NFT_NONCE_SHIFT: u32 = 32;
+
+//Las operaciones que hacemos con el NFT de un boleto
+#[derive(Clone, Copy)]
+enum NftStep {
+    Mint = 1,
+    Transfer = 2,
+    Burn = 3,
+    Reclaim = 4,
+    Return = 5,
+    Badge = 6,
+}
+
+impl Concert {
+    //Hace con el programa de tokens los pasos de una operacion de la cola
+    //Si la operacion ya se habia intentado, resumed, primero se revisa que pasos ya se hicieron
+    //Regresa false si alguna respuesta del programa de tokens no llego o fue un error
+    pub(super) async fn run_operation(
+        &mut self,
+        kind: &PendingOperationKind,
+        resumed: bool,
+    ) -> bool {
+        match kind.clone() {
+            PendingOperationKind::Mint {
+                buyer,
+                ticket_ids,
+                minted,
+                ..
+            } => {
+                if resumed {
+                    self.resume_tickets(buyer, &ticket_ids, minted).await
+                } else {
+                    self.issue_tickets(buyer, &ticket_ids).await
+                }
+            }
+            PendingOperationKind::Claim {
+                claimer,
+                ticket_ids,
+                ..
+            } => {
+                if resumed {
+                    self.resume_claim(claimer, &ticket_ids).await
+                } else {
+                    self.deliver_claimed_tickets(claimer, &ticket_ids).await
+                }
+            }
+            PendingOperationKind::Refund { holder, ticket_ids } => {
+                if resumed {
+                    self.resume_refund(holder, &ticket_ids).await
+                } else {
+                    self.reclaim_tickets(holder, &ticket_ids).await
+                }
+            }
+            PendingOperationKind::Badge { holder, token_id } => {
+                let badge = self.badge(holder, token_id);
+                self.issue_badge(&badge, resumed).await
+            }
+            PendingOperationKind::Hold { .. } => self.convert_tickets().await,
+        }
+    }
+
+    //Crea el token de una insignia de asistencia con su propio id y la metadata del concierto
+    //El token se mintea a nombre del concierto, que nunca lo transfiere, asi la insignia no se puede transferir
+    //Regresa false si la respuesta del programa de tokens no llego o fue un error
+    async fn issue_badge(&self, badge: &AttendanceBadge, resumed: bool) -> bool {
+        match self.backend {
+            //Con amount 1 el multitoken crea un NFT, si el concierto ya lo tiene el minteo anterior si se hizo
+            TokenBackend::Multitoken => {
+                if resumed {
+                    match self.contract_balance(badge.token_id).await {
+                        Some(0) => {}
+                        Some(_) => return true,
+                        None => return false,
+                    }
+                }
+                self.token_request::<_, MTKEvent>(MyMTKAction::MintBatch {
+                    ids: vec![badge.token_id],
+                    amounts: vec![1],
+                    tokens_metadata: vec![Some(badge.metadata())],
+                })
+                .await
+                .is_some()
+            }
+            //Un reintento repite el id de transaccion y el programa de NFTs no crea otro NFT
+            TokenBackend::NonFungibleToken => self
+                .token_request::<_, NFTEvent>(NFTAction::Mint {
+                    transaction_id: self.nft_transaction_id(NftStep::Badge, badge.token_id),
+                    token_metadata: nft_metadata(Some(badge.metadata())),
+                })
+                .await
+                .is_some(),
+            //El registro interno ya guarda la insignia en badges
+            TokenBackend::Internal => true,
+        }
+    }
+
+    //Crea los tokens de los boletos de una compra y se los entrega a quien recibe los boletos
+    //Los boletos de un cliente sin cartera se quedan en el concierto hasta que los reclame
+    //Regresa false si la respuesta del programa de tokens no llego o fue un error
+    async fn issue_tickets(&mut self, buyer: ActorId, ticket_ids: &[u128]) -> bool {
+        match self.backend {
+            //El multitoken crea el token fungible del concierto por la cantidad de boletos
+            //y el concierto, que es quien los mintea, se los transfiere al comprador
+            TokenBackend::Multitoken => {
+                let amount = ticket_ids.len() as u128;
+                if !self.mint_confirmed() {
+                    let mint = MyMTKAction::MintBatch {
+                        ids: vec![self.ticket_ft_id],
+                        amounts: vec![amount],
+                        tokens_metadata: vec![None],
+                    };
+                    if self.token_request::<_, MTKEvent>(mint).await.is_none() {
+                        return false;
+                    }
+                    self.confirm_mint();
+                }
+                if self.is_unclaimed(&buyer) {
+                    self.held_tokens += amount;
+                    return true;
+                }
+                let transfer = MyMTKAction::TransferFrom {
+                    from: exec::program_id(),
+                    to: buyer,
+                    id: self.ticket_ft_id,
+                    amount,
+                };
+                self.token_request::<_, MTKEvent>(transfer).await.is_some()
+            }
+            //El programa de NFTs crea un token por boleto con su metadata
+            TokenBackend::NonFungibleToken => {
+                for id in ticket_ids {
+                    if !self.issue_nft(buyer, *id).await {
+                        return false;
+                    }
+                }
+                true
+            }
+            //El registro interno solo suma los boletos al saldo del comprador
+            TokenBackend::Internal => {
+                *self.ledger.entry(buyer).or_default() += ticket_ids.len() as u128;
+                true
+            }
+        }
+    }
+
+    //Continua la creacion de los tokens de una compra que quedo pendiente
+    //En multitoken el saldo del concierto dice que paso con la respuesta que no llego:
+    //si tiene los tokens de la compra de mas el minteo si se hizo, y si ya no los tiene ya se entregaron
+    //En NFTs cada paso repite su id de transaccion, asi que no hace falta revisar nada
+    async fn resume_tickets(&mut self, buyer: ActorId, ticket_ids: &[u128], minted: bool) -> bool {
+        if self.backend == TokenBackend::Multitoken {
+            let balance = match self.contract_ft_balance().await {
+                Some(balance) => balance,
+                None => return false,
+            };
+            if balance >= self.kept_tokens() + ticket_ids.len() as u128 {
+                self.confirm_mint();
+            } else if minted {
+                return true;
+            }
+        }
+        self.issue_tickets(buyer, ticket_ids).await
+    }
+
+    //Continua la entrega de unos boletos de taquilla que quedo pendiente
+    //En multitoken si el concierto ya no tiene todos los tokens que guardaba la entrega si se hizo
+    async fn resume_claim(&mut self, claimer: ActorId, ticket_ids: &[u128]) -> bool {
+        if self.backend == TokenBackend::Multitoken {
+            let balance = match self.contract_ft_balance().await {
+                Some(balance) => balance,
+                None => return false,
+            };
+            if balance < self.kept_tokens() {
+                self.held_tokens -= ticket_ids.len() as u128;
+                return true;
+            }
+        }
+        self.deliver_claimed_tickets(claimer, ticket_ids).await
+    }
+
+    //Recupera los tokens de unos boletos que se van a reembolsar, el concierto se queda con ellos
+    //Quien tiene los boletos debe haber aprobado al concierto en el programa de tokens
+    async fn reclaim_tickets(&mut self, holder: ActorId, ticket_ids: &[u128]) -> bool {
+        match self.backend {
+            TokenBackend::Multitoken => {
+                let amount = ticket_ids.len() as u128;
+                let (ids, amounts) = self.multitoken_ticket_tokens(ticket_ids);
+                let transfer = MyMTKAction::BatchTransferFrom {
+                    from: holder,
+                    to: exec::program_id(),
+                    ids,
+                    amounts,
+                };
+                if self.token_request::<_, MTKEvent>(transfer).await.is_none() {
+                    return false;
+                }
+                self.returned_tokens += amount;
+                true
+            }
+            TokenBackend::NonFungibleToken => {
+                for id in ticket_ids {
+                    let token_id = match self.nft_tokens.get(id) {
+                        Some(token_id) => *token_id,
+                        None => return false,
+                    };
+                    let transfer = NFTAction::Transfer {
+                        transaction_id: self.nft_transaction_id(NftStep::Reclaim, *id),
+                        to: exec::program_id(),
+                        token_id,
+                    };
+                    if self.token_request::<_, NFTEvent>(transfer).await.is_none() {
+                        return false;
+                    }
+                }
+                true
+            }
+            //El registro interno solo le quita los boletos a quien los tenia
+            TokenBackend::Internal => {
+                if self.held_at.is_some() {
+                    for id in ticket_ids {
+                        self.ledger_nfts.remove(id);
+                    }
+                } else if let Some(balance) = self.ledger.get_mut(&holder) {
+                    *balance = balance.saturating_sub(ticket_ids.len() as u128);
+                }
+                true
+            }
+        }
+    }
+
+    //Continua la devolucion de unos boletos que quedo pendiente
+    //En multitoken si el concierto tiene los tokens de los boletos de mas la devolucion si se hizo
+    async fn resume_refund(&mut self, holder: ActorId, ticket_ids: &[u128]) -> bool {
+        if self.backend == TokenBackend::Multitoken {
+            let balance = match self.contract_ft_balance().await {
+                Some(balance) => balance,
+                None => return false,
+            };
+            if balance >= self.kept_tokens() + ticket_ids.len() as u128 {
+                self.returned_tokens += ticket_ids.len() as u128;
+                return true;
+            }
+        }
+        self.reclaim_tickets(holder, ticket_ids).await
+    }
+
+    //Le regresa a quien tenia los boletos los tokens que se recuperaron de un reembolso abortado
+    //Los tokens que no se habian recuperado no se tocan
+    pub(super) async fn return_reclaimed_tickets(&mut self, holder: ActorId, ticket_ids: &[u128]) {
+        match self.backend {
+            TokenBackend::Multitoken => {
+                let reclaimed = self.contract_ft_balance().await.map_or(false, |balance| {
+                    balance >= self.kept_tokens() + ticket_ids.len() as u128
+                });
+                if reclaimed {
+                    let (ids, amounts) = self.multitoken_ticket_tokens(ticket_ids);
+                    let transfer = MyMTKAction::BatchTransferFrom {
+                        from: exec::program_id(),
+                        to: holder,
+                        ids,
+                        amounts,
+                    };
+                    self.token_request::<_, MTKEvent>(transfer).await;
+                }
+            }
+            //Si el concierto no tiene el NFT la transferencia falla y el NFT se queda con quien lo tiene
+            TokenBackend::NonFungibleToken => {
+                for id in ticket_ids {
+                    if let Some(token_id) = self.nft_tokens.get(id).copied() {
+                        let transfer = NFTAction::Transfer {
+                            transaction_id: self.nft_transaction_id(NftStep::Return, *id),
+                            to: holder,
+                            token_id,
+                        };
+                        self.token_request::<_, NFTEvent>(transfer).await;
+                    }
+                }
+            }
+            //El registro interno no deja devoluciones pendientes
+            TokenBackend::Internal => {}
+        }
+    }
+
+    //Regresa los tokens de multitoken de unos boletos: el token fungible del concierto por la cantidad de boletos,
+    //y despues del hold tambien el NFT de cada boleto
+    fn multitoken_ticket_tokens(&self, ticket_ids: &[u128]) -> (Vec<TokenId>, Vec<u128>) {
+        let mut ids = vec![self.ticket_ft_id];
+        let mut amounts = vec![ticket_ids.len() as u128];
+        if self.held_at.is_some() {
+            ids.extend_from_slice(ticket_ids);
+            amounts.extend(iter::repeat(1).take(ticket_ids.len()));
+        }
+        (ids, amounts)
+    }
+
+    //Los tokens fungibles que el concierto debe tener: los de taquilla y los de boletos reembolsados
+    fn kept_tokens(&self) -> u128 {
+        self.held_tokens + self.returned_tokens
+    }
+
+    //Dice si ya se confirmo el minteo de la compra que se esta haciendo, que es la primera de la cola
+    fn mint_confirmed(&self) -> bool {
+        matches!(
+            self.pending_operations.first(),
+            Some(PendingOperation {
+                kind: PendingOperationKind::Mint { minted: true, .. },
+                ..
+            })
+        )
+    }
+
+    //Guarda en la compra que se esta haciendo que el minteo ya se confirmo
+    fn confirm_mint(&mut self) {
+        if let Some(PendingOperation {
+            kind: PendingOperationKind::Mint { minted, .. },
+            ..
+        }) = self.pending_operations.first_mut()
+        {
+            *minted = true;
+        }
+    }
+
+    //Regresa cuantos tokens fungibles del concierto tiene el propio smart contract
+    async fn contract_ft_balance(&self) -> Option<u128> {
+        self.contract_balance(self.ticket_ft_id).await
+    }
+
+    //Regresa cuantos tokens de un id de multitoken tiene el propio smart contract
+    async fn contract_balance(&self, id: TokenId) -> Option<u128> {
+        let balances = self
+            .token_request::<_, MTKEvent>(MyMTKAction::BalanceOfBatch {
+                accounts: vec![exec::program_id()],
+                ids: vec![id],
+            })
+            .await;
+        match balances {
+            Some(MTKEvent::BalanceOf(balances)) => Some(
+                balances
+                    .first()
+                    .map(|balance| balance.amount)
+                    .unwrap_or_default(),
+            ),
+            _ => None,
+        }
+    }
+
+    //El programa de NFTs mintea a quien manda el mensaje, que es el concierto,
+    //asi que despues de crear el NFT del boleto se lo transferimos al comprador
+    //Los ids de transaccion salen de la operacion y del id del boleto, un reintento recibe la misma respuesta
+    //del programa de NFTs sin crear ni transferir otro NFT
+    async fn issue_nft(&mut self, buyer: ActorId, ticket_id: u128) -> bool {
+        let token_id = match self.mint_nft(buyer, ticket_id).await {
+            Some(token_id) => token_id,
+            None => return false,
+        };
+        if self.is_unclaimed(&buyer) {
+            return true;
+        }
+        self.transfer_nft(buyer, ticket_id, token_id).await
+    }
+
+    //Transfiere el NFT de un boleto que tiene el concierto
+    async fn transfer_nft(&self, to: ActorId, ticket_id: u128, token_id: NftTokenId) -> bool {
+        let transfer = NFTAction::Transfer {
+            transaction_id: self.nft_transaction_id(NftStep::Transfer, ticket_id),
+            to,
+            token_id,
+        };
+        self.token_request::<_, NFTEvent>(transfer).await.is_some()
+    }
+
+    //Crea el NFT de un boleto y guarda su id, regresa None si no hubo respuesta
+    async fn mint_nft(&mut self, holder: ActorId, ticket_id: u128) -> Option<NftTokenId> {
+        if let Some(token_id) = self.nft_tokens.get(&ticket_id) {
+            return Some(*token_id);
+        }
+        let token_metadata = self
+            .metadata
+            .get(&holder)
+            .and_then(|tickets| tickets.get(&ticket_id))
+            .cloned()
+            .flatten();
+        let mint = NFTAction::Mint {
+            transaction_id: self.nft_transaction_id(NftStep::Mint, ticket_id),
+            token_metadata: nft_metadata(token_metadata),
+        };
+        match self.token_request::<_, NFTEvent>(mint).await {
+            Some(NFTEvent::Transfer(minted)) => {
+                self.nft_tokens.insert(ticket_id, minted.token_id);
+                Some(minted.token_id)
+            }
+            _ => None,
+        }
+    }
+
+    //Regresa el id de transaccion de un paso con el NFT de un boleto dentro de la operacion que se esta haciendo
+    //Todas las llamadas al programa de NFTs se hacen en la primera operacion de la cola
+    //El id de la operacion es creciente y se guarda en el estado, solo se repite despues de 2^28 operaciones
+    fn nft_transaction_id(&self, step: NftStep, ticket_id: u128) -> u64 {
+        let nonce = self
+            .pending_operations
+            .first()
+            .map_or(self.operation_counter, |pending| pending.id);
+        ((step as u64) << NFT_TRANSACTION_SHIFT)
+            | ((nonce & ((1 << (NFT_TRANSACTION_SHIFT - NFT_NONCE_SHIFT)) - 1)) << NFT_NONCE_SHIFT)
+            | (ticket_id as u64 & ((1 << NFT_NONCE_SHIFT) - 1))
+    }
+
+    //Dice si la cuenta es la direccion provisional de un cliente sin cartera que no ha reclamado sus boletos
+    fn is_unclaimed(&self, account: &ActorId) -> bool {
+        self.custodial_claims.get(account) == Some(&None)
+    }
+
+    //Entrega los tokens de los boletos de taquilla que guardaba el concierto a quien los reclamo
+    //Si el concierto ya se convirtio tambien entrega los NFTs de los boletos
+    async fn deliver_claimed_tickets(&mut self, claimer: ActorId, ticket_ids: &[u128]) -> bool {
+        match self.backend {
+            TokenBackend::Multitoken => {
+                let amount = ticket_ids.len() as u128;
+                let (ids, amounts) = self.multitoken_ticket_tokens(ticket_ids);
+                let transfer = MyMTKAction::BatchTransferFrom {
+                    from: exec::program_id(),
+                    to: claimer,
+                    ids,
+                    amounts,
+                };
+                if self.token_request::<_, MTKEvent>(transfer).await.is_none() {
+                    return false;
+                }
+                self.held_tokens -= amount;
+                true
+            }
+            TokenBackend::NonFungibleToken => {
+                for id in ticket_ids {
+                    let token_id = match self.mint_nft(claimer, *id).await {
+                        Some(token_id) => token_id,
+                        None => return false,
+                    };
+                    if !self.transfer_nft(claimer, *id, token_id).await {
+                        return false;
+                    }
+                }
+                true
+            }
+            //El registro interno ya movio los boletos al reclamarlos
+            TokenBackend::Internal => true,
+        }
+    }
+
+    //Destruye los tokens de una compra abortada que el concierto todavia tiene
+    //minted dice si el programa de tokens ya habia confirmado el minteo
+    //Regresa los boletos cuyo token no se pudo destruir, por ejemplo porque ya se le entrego al comprador
+    pub(super) async fn revoke_tickets(&mut self, ticket_ids: &[u128], minted: bool) -> Vec<u128> {
+        let mut unrevoked = Vec::new();
+        match self.backend {
+            TokenBackend::Multitoken => {
+                return self.revoke_multitoken_tickets(ticket_ids, minted).await
+            }
+            TokenBackend::NonFungibleToken => {}
+            TokenBackend::Internal => return unrevoked,
+        }
+        let holder = ticket_ids.first().and_then(|id| {
+            self.metadata
+                .iter()
+                .find_map(|(holder, tickets)| tickets.contains_key(id).then_some(*holder))
+        });
+        for id in ticket_ids {
+            //Si la respuesta del minteo no llego repetimos su transaccion,
+            //el programa de NFTs responde con el NFT que ya habia creado
+            let token_id = match holder {
+                Some(holder) => self.mint_nft(holder, *id).await,
+                None => self.nft_tokens.get(id).copied(),
+            };
+            let burned = match token_id {
+                Some(token_id) => {
+                    let burn = NFTAction::Burn {
+                        transaction_id: self.nft_transaction_id(NftStep::Burn, *id),
+                        token_id,
+                    };
+                    self.token_request::<_, NFTEvent>(burn).await.is_some()
+                }
+                None => false,
+            };
+            if burned {
+                self.nft_tokens.remove(id);
+            } else {
+                unrevoked.push(*id);
+            }
+        }
+        unrevoked
+    }
+
+    //Si los tokens de la compra se crearon pero no se transfirieron el concierto los tiene de mas
+    //sobre los que guarda para taquilla, en ese caso se destruyen
+    //Si no los tiene y el minteo no se confirmo nunca se crearon, por ejemplo si la compra seguia en la cola
+    async fn revoke_multitoken_tickets(&mut self, ticket_ids: &[u128], minted: bool) -> Vec<u128> {
+        let amount = ticket_ids.len() as u128;
+        let balance = match self.contract_ft_balance().await {
+            Some(balance) => balance,
+            None => return ticket_ids.to_vec(),
+        };
+        if balance < self.kept_tokens() + amount {
+            return if minted {
+                ticket_ids.to_vec()
+            } else {
+                Vec::new()
+            };
+        }
+        let burn = MyMTKAction::Burn {
+            id: self.ticket_ft_id,
+            amount,
+        };
+        match self.token_request::<_, MTKEvent>(burn).await {
+            Some(_) => Vec::new(),
+            None => ticket_ids.to_vec(),
+        }
+    }
+
+    //Convierte los boletos en NFTs continuando desde lo que guarda la operacion pendiente
+    //Regresa false si alguna respuesta del programa de tokens no llego o fue un error
+    async fn convert_tickets(&mut self) -> bool {
+        match self.backend {
+            TokenBackend::Multitoken => self.convert_multitoken_tickets().await,
+            //Los boletos ya son NFTs desde que se compraron
+            TokenBackend::NonFungibleToken => true,
+            //Cada boleto del registro interno pasa a ser de su comprador
+            TokenBackend::Internal => {
+                for (actor, tickets) in &self.metadata {
+                    for id in tickets.keys() {
+                        self.ledger_nfts.insert(*id, *actor);
+                    }
+                }
+                self.ledger.clear();
+                true
+            }
+        }
+    }
+
+    //En multitoken se crea un NFT por cada boleto y se le transfiere a su comprador
+    //Los tokens fungibles se quedan con los compradores como comprobante de compra,
+    //el concierto no puede destruir tokens de otra cuenta
+    async fn convert_multitoken_tickets(&mut self) -> bool {
+        let (mut minted, mut converted) = match self.pending_operations.first() {
+            Some(PendingOperation {
+                kind: PendingOperationKind::Hold { minted, converted },
+                ..
+            }) => (minted.clone(), converted.clone()),
+            _ => (Vec::new(), Vec::new()),
+        };
+        let buyers: Vec<ActorId> = self.buyers.iter().copied().collect();
+        for actor in buyers {
+            //Los compradores que ya tienen sus NFTs no se vuelven a procesar
+            if converted.contains(&actor) {
+                continue;
+            }
+            let mut ids = vec![];
+            let mut amounts = vec![];
+            let mut meta = vec![];
+            //Iteramos entre los tokens que tiene el comprador y los metemos en los vectores
+            for (token, token_meta) in self.metadata.get(&actor).cloned().unwrap_or_default() {
+                ids.push(token);
+                amounts.push(1);
+                meta.push(token_meta);
+            }
+            //Los NFTs que ya se crearon en un intento anterior no se vuelven a crear
+            if !ids.is_empty() && !minted.contains(&actor) {
+                //Convertimos a cada uno de estos tokens en un NFT con la funcion MintBatch de multitoken
+                //Cuando en amounts utilizas 1, la funcion los vuelve NFTs
+                let mint_batch = MyMTKAction::MintBatch {
+                    ids: ids.clone(),
+                    amounts: amounts.clone(),
+                    tokens_metadata: meta,
+                };
+                if self
+                    .token_request::<_, MTKEvent>(mint_batch)
+                    .await
+                    .is_none()
+                {
+                    return false;
+                }
+                minted.push(actor);
+                self.update_hold_progress(minted.clone(), converted.clone());
+            }
+            //Los NFTs de un cliente sin cartera se le entregan cuando reclame sus boletos
+            if !ids.is_empty() && !self.is_unclaimed(&actor) {
+                let transfer = MyMTKAction::BatchTransferFrom {
+                    from: exec::program_id(),
+                    to: actor,
+                    ids,
+                    amounts,
+                };
+                if self.token_request::<_, MTKEvent>(transfer).await.is_none() {
+                    return false;
+                }
+            }
+            converted.push(actor);
+            self.update_hold_progress(minted.clone(), converted.clone());
+        }
+        true
+    }
+
+    //Actualiza el avance de la conversion de boletos guardado en la operacion de la cola
+    fn update_hold_progress(&mut self, minted: Vec<ActorId>, converted: Vec<ActorId>) {
+        if let Some(pending) = self.pending_operations.first_mut() {
+            pending.kind = PendingOperationKind::Hold { minted, converted };
+        }
+    }
+
+    //Manda un mensaje al programa de tokens y espera su respuesta a lo mas TOKEN_REPLY_TIMEOUT bloques
+    //Regresa None si la respuesta no llego a tiempo o si el programa de tokens fallo
+    async fn token_request<A: Encode, E: Decode>(&self, action: A) -> Option<E> {
+        msg::send_for_reply_as::<_, E>(self.contract_id, action, 0)
+            .expect("Error in async message to token contract")
+            .up_to(Some(TOKEN_REPLY_TIMEOUT))
+            .expect("CONCERT: Invalid timeout for the token contract reply")
+            .await
+            .ok()
+    }
+}
+
+//Convierte la metadata de multitoken en la metadata del estandar de NFTs
+fn nft_metadata(metadata: Option<TokenMetadata>) -> NftMetadata {
+    let (name, description, media, reference) = metadata
+        .map(|meta| (meta.title, meta.description, meta.media, meta.reference))
+        .unwrap_or_default();
+    NftMetadata {
+        name: name.unwrap_or_default(),
+        description: description.unwrap_or_default(),
+        media: media.unwrap_or_default(),
+        reference: reference.unwrap_or_default(),
+    }
+}
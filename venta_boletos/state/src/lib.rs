@@ -23,4 +23,32 @@ pub mod metafns {
     pub fn user_tickets(state: State, user: ActorId) -> Vec<Option<TokenMetadata>> {
         state.user_tickets(user)
     }
+
+    pub fn available_seats(state: State, section: String) -> Vec<Seat> {
+        state.available_seats(section)
+    }
+
+    pub fn current_price(state: State, timestamp: u64) -> u128 {
+        state.current_price(timestamp)
+    }
+
+    pub fn waitlist_position(state: State, user: ActorId) -> Option<u32> {
+        state.waitlist_position(user)
+    }
+
+    pub fn promo_codes(state: State) -> Vec<([u8; 32], PromoCode)> {
+        state.promo_codes
+    }
+
+    pub fn partner_reports(state: State) -> Vec<(ActorId, PartnerQuota)> {
+        state.partner_quotas
+    }
+
+    pub fn announcements(state: State) -> Vec<Announcement> {
+        state.announcements
+    }
+
+    pub fn badges(state: State, user: ActorId) -> Vec<AttendanceBadge> {
+        state.user_badges(user)
+    }
 }
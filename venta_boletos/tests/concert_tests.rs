@@ -1,5 +1,8 @@
 use gear_lib::multitoken::io::*;
-use gstd::{prelude::*, ActorId, String};
+use gstd::{prelude::*, ActorId, Encode, String};
+use multitoken_io::MyMTKAction;
+use nft_io::{NFTAction, NFTEvent};
+use venta_boletos_io::*;
 
 mod utils;
 use utils::*;
@@ -131,3 +134,2517 @@ fn hold_concert() {
 
     hold(&concert_program, CONCERT_ID);
 }
+
+//Revisamos que las operaciones pendientes solo se puedan manejar cuando existen y por el dueño
+#[test]
+fn pending_operation_failures() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    // Debe fallar porque no hay ninguna operacion pendiente
+    let res = concert_program.send(USER, ConcertAction::RetryPendingOperation);
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::AbortPendingOperation);
+    assert!(res.main_failed());
+
+    // Debe fallar porque solo el dueño puede manejar las operaciones pendientes
+    let res = concert_program.send(OTHER_USER, ConcertAction::AbortPendingOperation);
+    assert!(res.main_failed());
+
+    //Una compra exitosa no deja operaciones pendientes
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.pending_operations.is_empty());
+}
+
+//Revisamos que una compra espere su turno mientras otra espera al programa de tokens,
+//y que la operacion sin respuesta quede pendiente para continuarla o abortarla
+#[test]
+fn token_contract_timeout() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    //El programa de tokens es una cuenta que nunca responde
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetTokenContract {
+            contract_id: SILENT_TOKEN_CONTRACT.into(),
+        },
+    );
+    assert!(!res.main_failed());
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    let purchase = |buyer: u64, ticket_id: u128| PendingOperationKind::Mint {
+        buyer: buyer.into(),
+        payer: buyer.into(),
+        amount: AMOUNT,
+        ticket_ids: vec![ticket_id],
+        request_id: None,
+        paid: 0,
+        fee: 0,
+        promo_code: None,
+        minted: false,
+    };
+    for buyer in [USER, OTHER_USER] {
+        let res = concert_program.send(
+            buyer,
+            ConcertAction::BuyTickets {
+                amount: AMOUNT,
+                metadata: vec![None],
+                request_id: None,
+                seats: Vec::new(),
+                promo_code: None,
+                recipient: None,
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    //La segunda compra no falla, espera su turno en la cola
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.pending_operations.len(), 2);
+    assert!(!state.pending_operations[0].interrupted);
+    assert_eq!(state.pending_operations[1].kind, purchase(OTHER_USER, 3));
+
+    // Debe fallar porque la primera compra sigue esperando la respuesta
+    let res = concert_program.send(OTHER_USER, ConcertAction::RetryPendingOperation);
+    assert!(res.main_failed());
+
+    //Se agota el tiempo de espera y la compra queda pendiente
+    let pending = ConcertEvent::OperationPending {
+        concert_id: CONCERT_ID,
+        kind: purchase(USER, 2),
+    }
+    .encode();
+    let results = system.spend_blocks(TOKEN_REPLY_BLOCKS);
+    assert!(results
+        .iter()
+        .any(|res| res.contains(&(USER, pending.clone()))));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.pending_operations[0].interrupted);
+
+    //Cualquiera la puede continuar, pero el programa de tokens sigue sin responder
+    let res = concert_program.send(OTHER_USER, ConcertAction::RetryPendingOperation);
+    assert!(!res.main_failed());
+    let pending = ConcertEvent::OperationPending {
+        concert_id: CONCERT_ID,
+        kind: purchase(USER, 2),
+    }
+    .encode();
+    let results = system.spend_blocks(TOKEN_REPLY_BLOCKS);
+    assert!(results
+        .iter()
+        .any(|res| res.contains(&(OTHER_USER, pending.clone()))));
+
+    // Debe fallar porque solo el dueño puede abortar las operaciones pendientes
+    let res = concert_program.send(OTHER_USER, ConcertAction::AbortPendingOperation);
+    assert!(res.main_failed());
+
+    //El dueño aborta la compra, como no se sabe si se crearon sus tokens el boleto queda registrado
+    let res = concert_program.send(USER, ConcertAction::AbortPendingOperation);
+    assert!(!res.main_failed());
+    let results = system.spend_blocks(TOKEN_REPLY_BLOCKS);
+    assert!(results.iter().any(|res| res.contains(&(
+        USER,
+        ConcertEvent::OperationAborted {
+            concert_id: CONCERT_ID,
+        }
+        .encode()
+    ))));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.orphaned_tickets, vec![2]);
+    assert_eq!(state.pending_operations.len(), 1);
+    assert_eq!(state.pending_operations[0].kind, purchase(OTHER_USER, 3));
+    assert_eq!(state.tickets_left, NUMBER_OF_TICKETS - AMOUNT);
+}
+
+//Revisamos que reenviar una compra con el mismo id de compra no compre otra vez
+#[test]
+fn buy_tickets_idempotent() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    for _ in 0..2 {
+        let res = concert_program.send(
+            USER,
+            ConcertAction::BuyTickets {
+                amount: AMOUNT,
+                metadata: vec![None],
+                request_id: Some(1),
+                seats: Vec::new(),
+                promo_code: None,
+                recipient: None,
+            },
+        );
+        assert!(res.contains(&(
+            USER,
+            ConcertEvent::Purchase {
+                concert_id: CONCERT_ID,
+                amount: AMOUNT,
+            }
+            .encode()
+        )));
+    }
+
+    //Solo se debio vender un boleto
+    check_current_concert(
+        &concert_program,
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        DATE,
+        NUMBER_OF_TICKETS,
+        NUMBER_OF_TICKETS - AMOUNT,
+    );
+}
+
+//Revisamos que la pausa detenga las compras y que solo el dueño la pueda usar
+#[test]
+fn pause_contract() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    // Debe fallar porque solo el dueño puede pausar
+    let res = concert_program.send(
+        OTHER_USER,
+        ConcertAction::Pause {
+            reason: String::from("Bug en la venta"),
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Pause {
+            reason: String::from("Bug en la venta"),
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Paused {
+            reason: String::from("Bug en la venta"),
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.pause_reason, Some(String::from("Bug en la venta")));
+
+    // Debe fallar porque el smart contract esta pausado
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], true);
+
+    let res = concert_program.send(USER, ConcertAction::Unpause);
+    assert!(res.contains(&(USER, ConcertEvent::Unpaused.encode())));
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+}
+
+//Revisamos que el cambio de dueño necesite la propuesta y la aceptacion
+#[test]
+fn transfer_ownership() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+
+    // Debe fallar porque nadie ha sido propuesto como dueño
+    let res = concert_program.send(OTHER_USER, ConcertAction::AcceptOwnership);
+    assert!(res.main_failed());
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::ProposeOwner {
+            new_owner: OTHER_USER.into(),
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::OwnerProposed {
+            new_owner: OTHER_USER.into(),
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.pending_owner, Some(OTHER_USER.into()));
+    assert_eq!(state.owner_id, USER.into());
+
+    let res = concert_program.send(OTHER_USER, ConcertAction::AcceptOwnership);
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::OwnershipTransferred {
+            previous_owner: USER.into(),
+            new_owner: OTHER_USER.into(),
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.pending_owner, None);
+    assert_eq!(state.owner_id, OTHER_USER.into());
+
+    // Debe fallar porque el dueño anterior ya no puede pausar
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Pause {
+            reason: String::from("Ya no soy el dueño"),
+        },
+    );
+    assert!(res.main_failed());
+}
+
+//Revisamos que el contrato de multitoken solo se cambie cuando no hay boletos sin convertir
+#[test]
+fn set_token_contract() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetTokenContract {
+            contract_id: NEW_MTK_ID.into(),
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::TokenContractChanged {
+            previous_contract: MTK_ID.into(),
+            new_contract: NEW_MTK_ID.into(),
+        }
+        .encode()
+    )));
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetTokenContract {
+            contract_id: MTK_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+
+    // Debe fallar porque hay boletos vendidos en el contrato actual
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetTokenContract {
+            contract_id: NEW_MTK_ID.into(),
+        },
+    );
+    assert!(res.main_failed());
+}
+
+//Revisamos que despues del hold el contrato de multitoken no cambie mientras los boletos se pueden reembolsar
+#[test]
+fn set_token_contract_during_dispute_period() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 0 },
+            revenue_split: Vec::new(),
+            dispute_period: 1_000_000_000,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+    hold(&concert_program, CONCERT_ID);
+
+    // Debe fallar porque los boletos se pueden reembolsar hasta que termine el periodo de disputa
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetTokenContract {
+            contract_id: NEW_MTK_ID.into(),
+        },
+    );
+    assert!(res.main_failed());
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.contract_id, MTK_ID.into());
+}
+
+//Revisamos en el smart contract de multi-token que los boletos lleguen al comprador y a quien se le regalan
+#[test]
+fn multitoken_backend_delivery() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    buy_and_gift(&concert_program);
+
+    //El concierto no se queda con ningun token
+    let accounts = [USER, OTHER_USER, CONCERT_PROGRAM_ID];
+    let mtk_program = system.get_program(MTK_ID);
+    let res = mtk_program.send(
+        USER,
+        MyMTKAction::BalanceOfBatch {
+            accounts: accounts.iter().map(|account| (*account).into()).collect(),
+            ids: vec![CONCERT_ID; accounts.len()],
+        },
+    );
+    let balances = accounts
+        .iter()
+        .zip([AMOUNT, AMOUNT, 0])
+        .map(|(account, amount)| BalanceReply {
+            account: (*account).into(),
+            id: CONCERT_ID,
+            amount,
+        })
+        .collect();
+    assert!(res.contains(&(USER, MTKEvent::BalanceOf(balances).encode())));
+}
+
+//Revisamos en el smart contract de NFTs que el NFT de cada boleto sea del comprador o de a quien se le regalo
+#[test]
+fn nft_backend_delivery() {
+    let system = init_system();
+    let concert_program = init_nft_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    buy_and_gift(&concert_program);
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.backend, TokenBackend::NonFungibleToken);
+    assert_eq!(state.nft_tokens.len(), 2);
+    let nft_program = system.get_program(NFT_ID);
+    //El primer boleto es de USER y el segundo, el regalo, de OTHER_USER
+    for (ticket_id, owner) in [(CONCERT_ID + 2, USER), (CONCERT_ID + 3, OTHER_USER)] {
+        let token_id = state
+            .nft_tokens
+            .iter()
+            .find(|(id, _)| *id == ticket_id)
+            .map(|(_, token_id)| *token_id)
+            .expect("The ticket should have an NFT");
+        let res = nft_program.send(USER, NFTAction::Owner { token_id });
+        assert!(res.contains(&(
+            USER,
+            NFTEvent::Owner {
+                owner: owner.into(),
+                token_id,
+            }
+            .encode()
+        )));
+    }
+}
+
+//Probamos el ciclo completo con el registro interno de boletos, sin el smart contract de multi-token
+#[test]
+fn internal_ledger_backend() {
+    let system = init_system();
+    let concert_program = init_internal_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    buy(
+        &concert_program,
+        CONCERT_ID,
+        AMOUNT + 1,
+        vec![None; 2],
+        false,
+    );
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.backend, TokenBackend::Internal);
+    assert_eq!(state.ledger, vec![(ActorId::from(USER), AMOUNT + 1)]);
+
+    hold(&concert_program, CONCERT_ID);
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.ledger.is_empty());
+    assert_eq!(state.ledger_nfts.len(), 2);
+    assert!(state
+        .ledger_nfts
+        .iter()
+        .all(|(_, owner)| *owner == ActorId::from(USER)));
+
+    // Debe fallar porque el registro interno no usa un contrato de tokens
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetTokenContract {
+            contract_id: MTK_ID.into(),
+        },
+    );
+    assert!(res.main_failed());
+}
+
+//Revisamos la venta de boletos con asientos asignados
+#[test]
+fn buy_assigned_seats() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let seat_map = vec![
+        SeatSection {
+            name: String::from("Platea"),
+            rows: vec![2, 2],
+        },
+        SeatSection {
+            name: String::from("Balcon"),
+            rows: vec![1],
+        },
+    ];
+
+    // Debe fallar porque el numero de boletos no coincide con los asientos
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: seat_map.clone(),
+            sale_mode: SaleMode::default(),
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: 5,
+            date: DATE,
+            seat_map,
+            sale_mode: SaleMode::default(),
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+
+    let seat = Seat {
+        section: String::from("Platea"),
+        row: 2,
+        number: 1,
+    };
+    let buy_seat = |seat: Seat| {
+        concert_program.send(
+            USER,
+            ConcertAction::BuyTickets {
+                amount: AMOUNT,
+                metadata: vec![None],
+                request_id: None,
+                seats: vec![seat],
+                promo_code: None,
+                recipient: None,
+            },
+        )
+    };
+    assert!(!buy_seat(seat.clone()).main_failed());
+
+    // Debe fallar porque el asiento ya se vendio
+    assert!(buy_seat(seat.clone()).main_failed());
+    // Debe fallar porque el asiento no existe
+    assert!(buy_seat(Seat {
+        section: String::from("Platea"),
+        row: 3,
+        number: 1,
+    })
+    .main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    let available = state.available_seats(String::from("Platea"));
+    assert_eq!(available.len(), 3);
+    assert!(!available.contains(&seat));
+}
+
+//Revisamos que se puedan apartar boletos y terminar la compra despues
+#[test]
+fn reserve_tickets() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    // Debe fallar porque no hay un concierto activo
+    let res = concert_program.send(
+        USER,
+        ConcertAction::ReserveTickets {
+            amount: AMOUNT,
+            seats: Vec::new(),
+        },
+    );
+    assert!(res.main_failed());
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    // Debe fallar porque no hay boletos apartados
+    let res = concert_program.send(
+        USER,
+        ConcertAction::CompletePurchase {
+            metadata: vec![None],
+        },
+    );
+    assert!(res.main_failed());
+
+    // Debe fallar porque una reservacion aparta a lo mas 10 boletos
+    let res = concert_program.send(
+        USER,
+        ConcertAction::ReserveTickets {
+            amount: 11,
+            seats: Vec::new(),
+        },
+    );
+    assert!(res.main_failed());
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::ReserveTickets {
+            amount: AMOUNT + 1,
+            seats: Vec::new(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    // Debe fallar porque el comprador ya tiene boletos apartados
+    let res = concert_program.send(
+        USER,
+        ConcertAction::ReserveTickets {
+            amount: AMOUNT,
+            seats: Vec::new(),
+        },
+    );
+    assert!(res.main_failed());
+
+    //Los boletos apartados ya no estan disponibles
+    check_current_concert(
+        &concert_program,
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        DATE,
+        NUMBER_OF_TICKETS,
+        NUMBER_OF_TICKETS - AMOUNT - 1,
+    );
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::CompletePurchase {
+            metadata: vec![None; 2],
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT + 1,
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.reservations.is_empty());
+    assert!(state.reservation_cooldowns.is_empty());
+    check_buyers(&concert_program, vec![ActorId::from(USER)]);
+}
+
+//Revisamos que quien deja expirar una reservacion no pueda volver a apartar los boletos en seguida
+#[test]
+fn reservation_cooldown() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    let reserve = || ConcertAction::ReserveTickets {
+        amount: AMOUNT,
+        seats: Vec::new(),
+    };
+    let res = concert_program.send(OTHER_USER, reserve());
+    assert!(!res.main_failed());
+    let state: State = concert_program.read_state().expect("Can't read state");
+    let expires_at = state.reservations[0].1.expires_at;
+    let (_, cooldown_ends) = state.reservation_cooldowns[0];
+
+    //Dejamos que la reservacion expire sin pagarla
+    while system.block_timestamp() <= expires_at {
+        system.spend_blocks(1);
+    }
+    // Debe fallar porque la reservacion anterior acaba de expirar
+    let res = concert_program.send(OTHER_USER, reserve());
+    assert!(res.main_failed());
+
+    //Despues de la espera ya puede apartar otra vez
+    while system.block_timestamp() < cooldown_ends {
+        system.spend_blocks(1);
+    }
+    let res = concert_program.send(OTHER_USER, reserve());
+    assert!(!res.main_failed());
+}
+
+//Revisamos que la lista de espera reciba los boletos que se regresan
+#[test]
+fn join_waitlist() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        AMOUNT,
+        DATE,
+        CONCERT_ID,
+    );
+
+    // Debe fallar porque todavia hay boletos
+    let res = concert_program.send(OTHER_USER, ConcertAction::JoinWaitlist { amount: AMOUNT });
+    assert!(res.main_failed());
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::ReserveTickets {
+            amount: AMOUNT,
+            seats: Vec::new(),
+        },
+    );
+    assert!(!res.main_failed());
+
+    let res = concert_program.send(OTHER_USER, ConcertAction::JoinWaitlist { amount: AMOUNT });
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::JoinedWaitlist {
+            concert_id: CONCERT_ID,
+            position: 1,
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.waitlist_position(OTHER_USER.into()), Some(1));
+
+    // Debe fallar porque ya esta en la lista de espera
+    let res = concert_program.send(OTHER_USER, ConcertAction::JoinWaitlist { amount: AMOUNT });
+    assert!(res.main_failed());
+}
+
+//Revisamos que los boletos reembolsados se le ofrezcan a la primera cuenta de la lista de espera
+#[test]
+fn waitlist_offer_after_refund() {
+    let system = init_system();
+    let concert_program = init_internal_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: 2,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+    let buy_ticket = |buyer: u64| {
+        concert_program.send_with_value(
+            buyer,
+            ConcertAction::BuyTickets {
+                amount: AMOUNT,
+                metadata: vec![None],
+                request_id: None,
+                seats: Vec::new(),
+                promo_code: None,
+                recipient: None,
+            },
+            1_000,
+        )
+    };
+    for buyer in [USER, OTHER_USER, THIRD_USER] {
+        system.mint_to(buyer, 10_000);
+    }
+    for buyer in [USER, OTHER_USER] {
+        assert!(!buy_ticket(buyer).main_failed());
+    }
+
+    //Ya no hay boletos, THIRD_USER y USER esperan en ese orden
+    for (position, account) in [THIRD_USER, USER].into_iter().enumerate() {
+        let res = concert_program.send(account, ConcertAction::JoinWaitlist { amount: AMOUNT });
+        assert!(res.contains(&(
+            account,
+            ConcertEvent::JoinedWaitlist {
+                concert_id: CONCERT_ID,
+                position: position as u32 + 1,
+            }
+            .encode()
+        )));
+    }
+
+    //Cambia la fecha y OTHER_USER pide el reembolso de su boleto
+    let res = concert_program.send(
+        USER,
+        ConcertAction::UpdateConcert {
+            name: None,
+            description: None,
+            date: Some(280623),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = concert_program.send(OTHER_USER, ConcertAction::RequestRescheduleRefund);
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::RescheduleRefund {
+            concert_id: CONCERT_ID,
+            amount: 1_000,
+            tickets: AMOUNT,
+        }
+        .encode()
+    )));
+
+    //El boleto regresado se le ofrece a THIRD_USER, que era el primero de la lista
+    let state: State = concert_program.read_state().expect("Can't read state");
+    let (account, offer) = &state.reservations[0];
+    assert_eq!(*account, ActorId::from(THIRD_USER));
+    assert_eq!(offer.amount, AMOUNT);
+    assert!(res.contains(&(
+        THIRD_USER,
+        ConcertEvent::WaitlistOffer {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+            expires_at: offer.expires_at,
+        }
+        .encode()
+    )));
+    assert_eq!(state.tickets_left, 0);
+    assert_eq!(state.waitlist_position(THIRD_USER.into()), None);
+    //USER avanza al primer lugar de la lista
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.waitlist_position(USER.into()), Some(1));
+
+    // Debe fallar porque el boleto ofrecido solo lo puede comprar THIRD_USER
+    assert!(buy_ticket(OTHER_USER).main_failed());
+    let res = concert_program.send_with_value(
+        THIRD_USER,
+        ConcertAction::CompletePurchase {
+            metadata: vec![None],
+        },
+        1_000,
+    );
+    assert!(res.contains(&(
+        THIRD_USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+        }
+        .encode()
+    )));
+}
+
+//Revisamos el sorteo completo con la garantia del creador y que se pueda repetir con los datos del estado
+#[test]
+fn lottery_draw() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        AMOUNT,
+        DATE,
+        CONCERT_ID,
+    );
+
+    let creator_seed = [7u8; 32];
+    let open = || ConcertAction::OpenLottery {
+        tickets_per_winner: AMOUNT,
+        seed_commitment: commitment(&creator_seed, USER.into()),
+    };
+    // Debe fallar porque el creador no deposita su garantia
+    let res = concert_program.send(USER, open());
+    assert!(res.main_failed());
+    system.mint_to(USER, 10_000);
+    let res = concert_program.send_with_value(USER, open(), 1_000);
+    assert!(!res.main_failed());
+
+    // Debe fallar porque los boletos se reparten por sorteo
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], true);
+
+    // Debe fallar porque el creador no puede participar en su sorteo
+    let res = concert_program.send(
+        USER,
+        ConcertAction::EnterLottery {
+            commitment: commitment(&[1u8; 32], USER.into()),
+        },
+    );
+    assert!(res.main_failed());
+
+    let participants = [(OTHER_USER, [1u8; 32]), (THIRD_USER, [2u8; 32])];
+    for (participant, secret) in participants {
+        let res = concert_program.send(
+            participant,
+            ConcertAction::EnterLottery {
+                commitment: commitment(&secret, participant.into()),
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    let res = concert_program.send(USER, ConcertAction::CloseLotteryRegistration);
+    assert!(!res.main_failed());
+
+    // Debe fallar porque el secreto no coincide con el compromiso
+    let res = concert_program.send(
+        OTHER_USER,
+        ConcertAction::RevealLotteryEntry { secret: [3u8; 32] },
+    );
+    assert!(res.main_failed());
+
+    for (participant, secret) in participants {
+        let res = concert_program.send(participant, ConcertAction::RevealLotteryEntry { secret });
+        assert!(!res.main_failed());
+    }
+
+    // Debe fallar porque la semilla no coincide con el compromiso
+    let res = concert_program.send(USER, ConcertAction::DrawLottery { seed: [8u8; 32] });
+    assert!(res.main_failed());
+
+    // Debe fallar porque el plazo para revelar no ha terminado
+    let res = concert_program.send(OTHER_USER, ConcertAction::ForceDrawLottery);
+    assert!(res.main_failed());
+
+    let res = concert_program.send(USER, ConcertAction::DrawLottery { seed: creator_seed });
+    assert!(!res.main_failed());
+    //El creador revelo su semilla a tiempo y se le regresa su garantia
+    assert!(res.contains(&(USER, Vec::<u8>::new())));
+
+    //Repetimos el sorteo con lo que guarda el estado
+    let state: State = concert_program.read_state().expect("Can't read state");
+    let lottery = state.lottery.expect("The lottery should be in the state");
+    assert_eq!(lottery.phase, LotteryPhase::Drawn);
+    let seed = lottery_seed(&creator_seed, &lottery.entries);
+    assert_eq!(lottery.seed, Some(seed));
+    assert_eq!(lottery.creator_bond, 1_000);
+    let winners = draw_winners(&seed, vec![OTHER_USER.into(), THIRD_USER.into()], 1);
+    assert_eq!(lottery.winners, winners);
+
+    //El ganador tiene sus boletos apartados
+    let (winner, reservation) = &state.reservations[0];
+    assert_eq!(*winner, winners[0]);
+    assert_eq!(reservation.amount, AMOUNT);
+}
+
+//Revisamos el precio de la subasta holandesa y que la compra cobre el precio actual
+#[test]
+fn dutch_auction() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::DutchAuction {
+                start_price: 10_000,
+                floor_price: 1_000,
+                start_time: 0,
+                end_time: 1_000,
+                uniform_price: false,
+            },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.sale_mode.price_at(0), 10_000);
+    assert_eq!(state.sale_mode.price_at(500), 5_500);
+    assert_eq!(state.current_price(2_000), 1_000);
+
+    system.mint_to(USER, 100_000);
+    // Debe fallar porque no se manda valor para pagar el boleto
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], true);
+
+    let res = concert_program.send_with_value(
+        USER,
+        ConcertAction::BuyTickets {
+            amount: AMOUNT,
+            metadata: vec![None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: None,
+        },
+        10_000,
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.proceeds >= 1_000 && state.proceeds <= 10_000);
+}
+
+//Revisamos las reglas para ofertar en la subasta de ofertas selladas
+#[test]
+fn sealed_bid_auction() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    // Debe fallar porque solo el creador puede iniciar la subasta
+    let start = ConcertAction::StartSealedBidAuction {
+        tickets: 2,
+        seats: Vec::new(),
+        commit_deadline: 1_000_000,
+        reveal_deadline: 2_000_000,
+    };
+    assert!(concert_program.send(OTHER_USER, start).main_failed());
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::StartSealedBidAuction {
+            tickets: 2,
+            seats: Vec::new(),
+            commit_deadline: 1_000_000,
+            reveal_deadline: 2_000_000,
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::SealedBidAuctionStarted {
+            concert_id: CONCERT_ID,
+            tickets: 2,
+        }
+        .encode()
+    )));
+
+    //Los boletos de la subasta se separan de la venta al publico
+    check_current_concert(
+        &concert_program,
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        DATE,
+        NUMBER_OF_TICKETS,
+        NUMBER_OF_TICKETS - 2,
+    );
+
+    let salt = [5u8; 32];
+    let commitment = bid_commitment(5_000, &salt, OTHER_USER.into());
+    // Debe fallar porque la oferta necesita un deposito
+    let res = concert_program.send(OTHER_USER, ConcertAction::CommitBid { commitment });
+    assert!(res.main_failed());
+
+    system.mint_to(OTHER_USER, 100_000);
+    let res =
+        concert_program.send_with_value(OTHER_USER, ConcertAction::CommitBid { commitment }, 5_000);
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::BidCommitted {
+            concert_id: CONCERT_ID,
+        }
+        .encode()
+    )));
+
+    // Debe fallar porque todavia no es el periodo para revelar
+    let res = concert_program.send(
+        OTHER_USER,
+        ConcertAction::RevealBid {
+            amount: 5_000,
+            salt,
+            metadata: None,
+        },
+    );
+    assert!(res.main_failed());
+
+    // Debe fallar porque la subasta no ha terminado
+    let res = concert_program.send(USER, ConcertAction::SettleSealedBidAuction);
+    assert!(res.main_failed());
+}
+
+//Revisamos la subasta de ofertas selladas completa: ofertas, revelado, liquidacion y reclamo de los boletos
+#[test]
+fn sealed_bid_auction_settlement() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: 4,
+            date: DATE,
+            seat_map: vec![SeatSection {
+                name: String::from("Platea"),
+                rows: vec![2, 2],
+            }],
+            sale_mode: SaleMode::default(),
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+    let seat = |number: u32| Seat {
+        section: String::from("Platea"),
+        row: 1,
+        number,
+    };
+    let commit_deadline = system.block_timestamp() + 5_000;
+    let reveal_deadline = commit_deadline + 5_000;
+    let start = || ConcertAction::StartSealedBidAuction {
+        tickets: 2,
+        seats: vec![seat(1), seat(2)],
+        commit_deadline,
+        reveal_deadline,
+    };
+    let res = concert_program.send(USER, start());
+    assert!(!res.main_failed());
+
+    //Cada postor manda su compromiso con un deposito que cubre su oferta
+    let bids = [
+        (OTHER_USER, 5_000, 6_000, [1u8; 32]),
+        (THIRD_USER, 3_000, 3_000, [2u8; 32]),
+        (USER, 1_000, 2_000, [3u8; 32]),
+    ];
+    for (bidder, amount, deposit, salt) in bids {
+        system.mint_to(bidder, 10_000);
+        let res = concert_program.send_with_value(
+            bidder,
+            ConcertAction::CommitBid {
+                commitment: bid_commitment(amount, &salt, bidder.into()),
+            },
+            deposit,
+        );
+        assert!(!res.main_failed());
+    }
+
+    while system.block_timestamp() <= commit_deadline {
+        system.spend_blocks(1);
+    }
+    for (bidder, amount, _, salt) in bids {
+        let res = concert_program.send(
+            bidder,
+            ConcertAction::RevealBid {
+                amount,
+                salt,
+                metadata: None,
+            },
+        );
+        assert!(!res.main_failed());
+    }
+
+    while system.block_timestamp() <= reveal_deadline {
+        system.spend_blocks(1);
+    }
+    let res = concert_program.send(USER, ConcertAction::SettleSealedBidAuction);
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::SealedBidAuctionSettled {
+            concert_id: CONCERT_ID,
+            winners: vec![(OTHER_USER.into(), 5_000), (THIRD_USER.into(), 3_000)],
+        }
+        .encode()
+    )));
+    //Al ganador se le regresa lo que deposito de mas y al que perdio todo su deposito
+    assert!(res.contains(&(OTHER_USER, Vec::<u8>::new())));
+    assert!(res.contains(&(USER, Vec::<u8>::new())));
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.proceeds, 8_000);
+    let win = |winner: u64| {
+        state
+            .auction_wins
+            .iter()
+            .find(|(account, _)| *account == ActorId::from(winner))
+            .map(|(_, win)| win.clone())
+            .expect("The winner should have a ticket to claim")
+    };
+    assert_eq!(win(OTHER_USER).seat, Some(seat(1)));
+    assert_eq!(win(OTHER_USER).paid, 5_000);
+    assert_eq!(win(THIRD_USER).seat, Some(seat(2)));
+    assert_eq!(win(THIRD_USER).paid, 3_000);
+
+    // Debe fallar porque los ganadores no han reclamado sus boletos
+    let res = concert_program.send(USER, start());
+    assert!(res.main_failed());
+    // Debe fallar porque USER no gano la subasta
+    let res = concert_program.send(USER, ConcertAction::ClaimAuctionTicket);
+    assert!(res.main_failed());
+
+    for winner in [OTHER_USER, THIRD_USER] {
+        let res = concert_program.send(winner, ConcertAction::ClaimAuctionTicket);
+        assert!(res.contains(&(
+            winner,
+            ConcertEvent::Purchase {
+                concert_id: CONCERT_ID,
+                amount: AMOUNT,
+            }
+            .encode()
+        )));
+    }
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.auction_wins.is_empty());
+    let seat_holder = |seat: Seat| {
+        let ticket_id = state
+            .sold_seats
+            .iter()
+            .find(|(sold, _)| *sold == seat)
+            .map(|(_, id)| *id)
+            .expect("The seat should be sold");
+        state
+            .metadata
+            .iter()
+            .find(|(_, tickets)| tickets.iter().any(|(id, _)| *id == ticket_id))
+            .map(|(holder, _)| *holder)
+    };
+    assert_eq!(seat_holder(seat(1)), Some(OTHER_USER.into()));
+    assert_eq!(seat_holder(seat(2)), Some(THIRD_USER.into()));
+    check_current_concert(
+        &concert_program,
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        DATE,
+        4,
+        2,
+    );
+}
+
+//Revisamos que al crear un concierto no queden reservaciones, lista de espera ni subasta del anterior
+#[test]
+fn create_clears_previous_concert() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        2,
+        DATE,
+        CONCERT_ID,
+    );
+    let res = concert_program.send(
+        OTHER_USER,
+        ConcertAction::ReserveTickets {
+            amount: AMOUNT,
+            seats: Vec::new(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = concert_program.send(
+        USER,
+        ConcertAction::StartSealedBidAuction {
+            tickets: 1,
+            seats: Vec::new(),
+            commit_deadline: 1_000_000,
+            reveal_deadline: 2_000_000,
+        },
+    );
+    assert!(!res.main_failed());
+    system.mint_to(OTHER_USER, 100_000);
+    let commitment = bid_commitment(5_000, &[5u8; 32], OTHER_USER.into());
+    let res =
+        concert_program.send_with_value(OTHER_USER, ConcertAction::CommitBid { commitment }, 5_000);
+    assert!(!res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::JoinWaitlist { amount: AMOUNT });
+    assert!(!res.main_failed());
+    hold(&concert_program, CONCERT_ID);
+
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en Monterrey 28/06/2023"),
+        NUMBER_OF_TICKETS,
+        280623,
+        CONCERT_ID,
+    );
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.reservations.is_empty());
+    assert!(state.waitlist.is_empty());
+    assert_eq!(state.sealed_auction, None);
+    assert!(state.auction_wins.is_empty());
+    check_current_concert(
+        &concert_program,
+        String::from("Stromae"),
+        String::from("Stromae en Monterrey 28/06/2023"),
+        280623,
+        NUMBER_OF_TICKETS,
+        NUMBER_OF_TICKETS,
+    );
+
+    //El nuevo concierto puede empezar su propia subasta
+    let res = concert_program.send(
+        USER,
+        ConcertAction::StartSealedBidAuction {
+            tickets: 1,
+            seats: Vec::new(),
+            commit_deadline: 1_000_000,
+            reveal_deadline: 2_000_000,
+        },
+    );
+    assert!(!res.main_failed());
+}
+
+//Revisamos que los codigos de descuento se validen, descuenten y se cuenten sus usos
+#[test]
+fn promo_codes() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+
+    let code_hash = promo_code_hash(CONCERT_ID, "STROMAE20");
+    let add = || ConcertAction::AddPromoCode {
+        code_hash,
+        discount_percent: 20,
+        max_uses: 1,
+        expires_at: 1_000_000,
+    };
+    // Debe fallar porque solo el creador puede registrar codigos
+    let res = concert_program.send(OTHER_USER, add());
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, add());
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::PromoCodeAdded {
+            concert_id: CONCERT_ID,
+            code_hash,
+        }
+        .encode()
+    )));
+
+    system.mint_to(USER, 10_000);
+    let buy_with_code = |code: &str| ConcertAction::BuyTickets {
+        amount: AMOUNT,
+        metadata: vec![None],
+        request_id: None,
+        seats: Vec::new(),
+        promo_code: Some(String::from(code)),
+        recipient: None,
+    };
+    // Debe fallar porque el codigo no esta registrado
+    let res = concert_program.send_with_value(USER, buy_with_code("STROMAE50"), 800);
+    assert!(res.main_failed());
+    // Debe fallar porque el valor no alcanza ni con el descuento
+    let res = concert_program.send_with_value(USER, buy_with_code("STROMAE20"), 700);
+    assert!(res.main_failed());
+    // Debe fallar porque el codigo solo alcanza para un boleto
+    let res = concert_program.send_with_value(
+        USER,
+        ConcertAction::BuyTickets {
+            amount: 2,
+            metadata: vec![None, None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: Some(String::from("STROMAE20")),
+            recipient: None,
+        },
+        1_600,
+    );
+    assert!(res.main_failed());
+
+    let res = concert_program.send_with_value(USER, buy_with_code("STROMAE20"), 800);
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+        }
+        .encode()
+    )));
+    // Debe fallar porque el codigo ya no tiene usos
+    let res = concert_program.send_with_value(USER, buy_with_code("STROMAE20"), 800);
+    assert!(res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.proceeds, 800);
+    assert_eq!(state.promo_codes.len(), 1);
+    assert_eq!(state.promo_codes[0].1.remaining_uses(), 0);
+}
+
+//Revisamos que el precio se cobre segun la fase activa y que la fase se vea en el concierto actual
+#[test]
+fn price_schedule() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let create = |phases: Vec<PricePhase>| ConcertAction::Create {
+        creator: USER.into(),
+        name: String::from("Stromae"),
+        description: String::from("Stromae en la CDMX 21/06/2023"),
+        number_of_tickets: NUMBER_OF_TICKETS,
+        date: DATE,
+        seat_map: Vec::new(),
+        sale_mode: SaleMode::Scheduled { phases },
+        revenue_split: Vec::new(),
+        dispute_period: 0,
+        complimentary_tickets: 0,
+    };
+    let early_bird = PricePhase {
+        name: String::from("Preventa"),
+        price: 500,
+        starts_at: 0,
+    };
+    let regular = PricePhase {
+        name: String::from("General"),
+        price: 1_000,
+        starts_at: 1_000_000_000,
+    };
+    // Debe fallar porque las fases no estan ordenadas
+    let res = concert_program.send(USER, create(vec![regular.clone(), early_bird.clone()]));
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, create(vec![early_bird.clone(), regular]));
+    assert!(!res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.current_price(2_000_000_000), 1_000);
+    assert_eq!(state.current_concert().price_phase, Some(early_bird));
+
+    system.mint_to(USER, 10_000);
+    let buy_tickets = || ConcertAction::BuyTickets {
+        amount: AMOUNT,
+        metadata: vec![None],
+        request_id: None,
+        seats: Vec::new(),
+        promo_code: None,
+        recipient: None,
+    };
+    // Debe fallar porque el valor no alcanza para el precio de preventa
+    let res = concert_program.send_with_value(USER, buy_tickets(), 400);
+    assert!(res.main_failed());
+    let res = concert_program.send_with_value(USER, buy_tickets(), 500);
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.proceeds, 500);
+}
+
+//Revisamos que cada beneficiario retire su parte de lo recaudado solo despues del hold
+#[test]
+fn revenue_split() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let create = |revenue_split: Vec<(ActorId, u8)>| ConcertAction::Create {
+        creator: USER.into(),
+        name: String::from("Stromae"),
+        description: String::from("Stromae en la CDMX 21/06/2023"),
+        number_of_tickets: NUMBER_OF_TICKETS,
+        date: DATE,
+        seat_map: Vec::new(),
+        sale_mode: SaleMode::FixedPrice { price: 1_000 },
+        revenue_split,
+        dispute_period: 0,
+        complimentary_tickets: 0,
+    };
+    // Debe fallar porque los porcentajes no suman 100
+    let res = concert_program.send(
+        USER,
+        create(vec![(USER.into(), 70), (OTHER_USER.into(), 20)]),
+    );
+    assert!(res.main_failed());
+    let res = concert_program.send(
+        USER,
+        create(vec![(USER.into(), 70), (OTHER_USER.into(), 30)]),
+    );
+    assert!(!res.main_failed());
+
+    system.mint_to(USER, 10_000);
+    let res = concert_program.send_with_value(
+        USER,
+        ConcertAction::BuyTickets {
+            amount: AMOUNT,
+            metadata: vec![None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: None,
+        },
+        1_000,
+    );
+    assert!(!res.main_failed());
+
+    // Debe fallar porque el concierto no se ha hecho hold
+    let res = concert_program.send(OTHER_USER, ConcertAction::Withdraw);
+    assert!(res.main_failed());
+
+    hold(&concert_program, CONCERT_ID);
+    let res = concert_program.send(OTHER_USER, ConcertAction::Withdraw);
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::Withdrawn {
+            concert_id: CONCERT_ID,
+            amount: 300,
+        }
+        .encode()
+    )));
+    // Debe fallar porque ya retiro toda su parte
+    let res = concert_program.send(OTHER_USER, ConcertAction::Withdraw);
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::Withdraw);
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Withdrawn {
+            concert_id: CONCERT_ID,
+            amount: 700,
+        }
+        .encode()
+    )));
+}
+
+//Revisamos que lo que no se retiro de un concierto se guarde para cada beneficiario al crear el siguiente
+#[test]
+fn carried_revenue() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: vec![(USER.into(), 70), (OTHER_USER.into(), 30)],
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+    system.mint_to(USER, 10_000);
+    let res = concert_program.send_with_value(
+        USER,
+        ConcertAction::BuyTickets {
+            amount: AMOUNT,
+            metadata: vec![None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: None,
+        },
+        1_000,
+    );
+    assert!(!res.main_failed());
+    hold(&concert_program, CONCERT_ID);
+    let res = concert_program.send(USER, ConcertAction::Withdraw);
+    assert!(!res.main_failed());
+
+    //OTHER_USER no retiro su parte, el siguiente concierto se crea de todos modos
+    let new_concert_id = CONCERT_ID + AMOUNT;
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en Monterrey 28/06/2023"),
+        NUMBER_OF_TICKETS,
+        280623,
+        new_concert_id,
+    );
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(
+        state.carried_revenue,
+        vec![(ActorId::from(OTHER_USER), 300)]
+    );
+    assert!(state.withdrawals.is_empty());
+
+    //Lo guardado se retira aunque el nuevo concierto no haya terminado
+    let res = concert_program.send(OTHER_USER, ConcertAction::Withdraw);
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::Withdrawn {
+            concert_id: new_concert_id,
+            amount: 300,
+        }
+        .encode()
+    )));
+    // Debe fallar porque ya retiro lo guardado y el nuevo concierto no ha terminado
+    let res = concert_program.send(OTHER_USER, ConcertAction::Withdraw);
+    assert!(res.main_failed());
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.carried_revenue.is_empty());
+}
+
+//Revisamos que la comision de la plataforma se separe de lo recaudado y la retire el dueño
+#[test]
+fn platform_fee() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    // Debe fallar porque solo el dueño puede cambiar la comision
+    let res = concert_program.send(
+        OTHER_USER,
+        ConcertAction::SetPlatformFee {
+            fee: Some(PlatformFee::Percentage(10)),
+        },
+    );
+    assert!(res.main_failed());
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetPlatformFee {
+            fee: Some(PlatformFee::Percentage(10)),
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::PlatformFeeChanged {
+            fee: Some(PlatformFee::Percentage(10)),
+        }
+        .encode()
+    )));
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+
+    system.mint_to(OTHER_USER, 10_000);
+    let res = concert_program.send_with_value(
+        OTHER_USER,
+        ConcertAction::BuyTickets {
+            amount: AMOUNT,
+            metadata: vec![None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: None,
+        },
+        1_000,
+    );
+    assert!(!res.main_failed());
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.proceeds, 900);
+    assert_eq!(state.fee_balance, 100);
+    assert_eq!(state.total_fees, 100);
+
+    // Debe fallar porque el concierto sigue activo
+    let res = concert_program.send(USER, ConcertAction::WithdrawFees);
+    assert!(res.main_failed());
+
+    hold(&concert_program, CONCERT_ID);
+    let res = concert_program.send(USER, ConcertAction::WithdrawFees);
+    assert!(res.contains(&(USER, ConcertEvent::FeesWithdrawn { amount: 100 }.encode())));
+    let res = concert_program.send(USER, ConcertAction::Withdraw);
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Withdrawn {
+            concert_id: CONCERT_ID,
+            amount: 900,
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.fee_balance, 0);
+    assert_eq!(state.total_fees, 100);
+}
+
+//Revisamos que lo pagado se quede en escrow durante el periodo de disputa y se reembolse al cancelar
+#[test]
+fn escrow_and_cancel() {
+    let system = init_system();
+    let buy_tickets = || ConcertAction::BuyTickets {
+        amount: AMOUNT,
+        metadata: vec![None],
+        request_id: None,
+        seats: Vec::new(),
+        promo_code: None,
+        recipient: None,
+    };
+    let create = || ConcertAction::Create {
+        creator: USER.into(),
+        name: String::from("Stromae"),
+        description: String::from("Stromae en la CDMX 21/06/2023"),
+        number_of_tickets: NUMBER_OF_TICKETS,
+        date: DATE,
+        seat_map: Vec::new(),
+        sale_mode: SaleMode::FixedPrice { price: 1_000 },
+        revenue_split: Vec::new(),
+        dispute_period: 1_000_000_000,
+        complimentary_tickets: 0,
+    };
+    system.mint_to(OTHER_USER, 10_000);
+
+    let concert_program = init_concert(&system);
+    assert!(!concert_program.send(USER, create()).main_failed());
+    assert!(!concert_program
+        .send_with_value(OTHER_USER, buy_tickets(), 1_000)
+        .main_failed());
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(
+        state.escrow,
+        vec![(
+            OTHER_USER.into(),
+            EscrowPayment {
+                paid: 1_000,
+                fee: 0
+            }
+        )]
+    );
+    hold(&concert_program, CONCERT_ID);
+    // Debe fallar porque no ha terminado el periodo de disputa
+    let res = concert_program.send(USER, ConcertAction::Withdraw);
+    assert!(res.main_failed());
+    // Debe fallar porque el concierto ya se hizo hold
+    let res = concert_program.send(USER, ConcertAction::CancelConcert);
+    assert!(res.main_failed());
+
+    let concert_program = init_internal_concert(&system);
+    assert!(!concert_program.send(USER, create()).main_failed());
+    assert!(!concert_program
+        .send_with_value(OTHER_USER, buy_tickets(), 1_000)
+        .main_failed());
+    // Debe fallar porque solo el creador o el dueño pueden cancelar
+    let res = concert_program.send(OTHER_USER, ConcertAction::CancelConcert);
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::CancelConcert);
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::ConcertCancelled {
+            concert_id: CONCERT_ID,
+            refunded: 1_000,
+        }
+        .encode()
+    )));
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.cancelled);
+    assert!(state.escrow.is_empty());
+    assert_eq!(state.proceeds, 0);
+    // Debe fallar porque el concierto fue cancelado
+    let res = concert_program.send_with_value(OTHER_USER, buy_tickets(), 1_000);
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::Hold);
+    assert!(res.main_failed());
+}
+
+//Revisamos que durante el periodo de disputa se puedan regresar los boletos y que las comisiones esperen
+#[test]
+fn dispute_refund() {
+    let system = init_system();
+    let concert_program = init_internal_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: Vec::new(),
+            dispute_period: 1_000_000_000,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+
+    system.mint_to(OTHER_USER, 10_000);
+    let res = concert_program.send_with_value(
+        OTHER_USER,
+        ConcertAction::BuyTickets {
+            amount: AMOUNT,
+            metadata: vec![None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: None,
+        },
+        1_000,
+    );
+    assert!(!res.main_failed());
+    let ticket_id = CONCERT_ID + 2;
+    let refund = || ConcertAction::RequestRefund {
+        ticket_ids: vec![ticket_id],
+    };
+    // Debe fallar porque el periodo de disputa empieza con el hold
+    let res = concert_program.send(OTHER_USER, refund());
+    assert!(res.main_failed());
+
+    hold(&concert_program, CONCERT_ID);
+    // Debe fallar porque las comisiones se guardan hasta que termine el periodo de disputa
+    let res = concert_program.send(USER, ConcertAction::WithdrawFees);
+    assert!(res.main_failed());
+    // Debe fallar porque el boleto no es de quien pide el reembolso
+    let res = concert_program.send(USER, refund());
+    assert!(res.main_failed());
+
+    let res = concert_program.send(OTHER_USER, refund());
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::Refunded {
+            concert_id: CONCERT_ID,
+            amount: 1_000,
+            tickets: 1,
+        }
+        .encode()
+    )));
+    // Debe fallar porque el boleto ya se reembolso
+    let res = concert_program.send(OTHER_USER, refund());
+    assert!(res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert!(state.escrow.is_empty());
+    assert!(state.ticket_payments.is_empty());
+    assert!(state.ledger_nfts.is_empty());
+    assert_eq!(state.proceeds, 0);
+    assert!(state.user_tickets(OTHER_USER.into()).is_empty());
+}
+
+//Revisamos que los boletos de cortesia salgan de su propia reserva y queden marcados
+#[test]
+fn issue_complimentary() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 2,
+        },
+    );
+    assert!(!res.main_failed());
+
+    let issue = |amount: u128| ConcertAction::IssueComplimentary {
+        to: OTHER_USER.into(),
+        amount,
+        metadata: vec![None; amount as usize],
+    };
+    // Debe fallar porque solo el creador puede dar boletos de cortesia
+    let res = concert_program.send(OTHER_USER, issue(1));
+    assert!(res.main_failed());
+    // Debe fallar porque no hay tantos boletos de cortesia
+    let res = concert_program.send(USER, issue(3));
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, issue(2));
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: 2,
+        }
+        .encode()
+    )));
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.tickets_left, NUMBER_OF_TICKETS);
+    assert_eq!(state.complimentary_left, 0);
+    assert_eq!(state.complimentary_tickets.len(), 2);
+    assert_eq!(state.proceeds, 0);
+    assert_eq!(state.user_tickets(OTHER_USER.into()).len(), 2);
+}
+
+//Revisamos que las agencias vendan solo hasta su cuota y que sus ventas queden en el reporte
+#[test]
+fn partner_quotas() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    let sell = |amount: u128| ConcertAction::PartnerSell {
+        to: USER.into(),
+        amount,
+        metadata: vec![None; amount as usize],
+    };
+    // Debe fallar porque la agencia no tiene cuota
+    let res = concert_program.send(OTHER_USER, sell(1));
+    assert!(res.main_failed());
+    let assign = || ConcertAction::AssignPartnerQuota {
+        partner: OTHER_USER.into(),
+        quota: 3,
+    };
+    // Debe fallar porque solo el creador asigna cuotas
+    let res = concert_program.send(OTHER_USER, assign());
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, assign());
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::PartnerQuotaAssigned {
+            concert_id: CONCERT_ID,
+            partner: OTHER_USER.into(),
+            quota: 3,
+        }
+        .encode()
+    )));
+
+    let res = concert_program.send(OTHER_USER, sell(2));
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: 2,
+        }
+        .encode()
+    )));
+    // Debe fallar porque solo le queda un boleto de su cuota
+    let res = concert_program.send(OTHER_USER, sell(2));
+    assert!(res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.tickets_left, NUMBER_OF_TICKETS - 3);
+    assert_eq!(
+        state.partner_quotas,
+        vec![(
+            OTHER_USER.into(),
+            PartnerQuota {
+                quota: 3,
+                sold: 2,
+                revenue: 0,
+            }
+        )]
+    );
+    assert_eq!(state.partner_tickets.len(), 2);
+    assert_eq!(state.user_tickets(USER.into()).len(), 2);
+}
+
+//Revisamos las ventas de taquilla para clientes sin cartera y que los boletos se reclamen una vez
+#[test]
+fn box_office_operator() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+
+    let secret = [9u8; 32];
+    let claim_hash = claim_secret_hash(&secret);
+    let box_office_sale = || ConcertAction::BuyTickets {
+        amount: AMOUNT,
+        metadata: vec![None],
+        request_id: None,
+        seats: Vec::new(),
+        promo_code: None,
+        recipient: Some(TicketRecipient::Custodial { claim_hash }),
+    };
+    // Debe fallar porque solo un operador puede comprar para un cliente sin cartera
+    let res = concert_program.send(OTHER_USER, box_office_sale());
+    assert!(res.main_failed());
+
+    let res = concert_program.send(
+        USER,
+        ConcertAction::SetOperator {
+            operator: OTHER_USER.into(),
+            enabled: true,
+        },
+    );
+    assert!(!res.main_failed());
+    let res = concert_program.send(OTHER_USER, box_office_sale());
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+        }
+        .encode()
+    )));
+    // Debe fallar porque el secreto ya se uso en otra venta
+    let res = concert_program.send(OTHER_USER, box_office_sale());
+    assert!(res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.operator_sales.len(), 1);
+    assert_eq!(
+        state.operator_sales[0].1[0].recipient,
+        custodial_account(claim_hash)
+    );
+
+    // Debe fallar porque el secreto no es el correcto
+    let res = concert_program.send(USER, ConcertAction::ClaimTickets { secret: [8u8; 32] });
+    assert!(res.main_failed());
+    // Debe fallar porque no se comprometio con el secreto antes de reclamar
+    let res = concert_program.send(USER, ConcertAction::ClaimTickets { secret });
+    assert!(res.main_failed());
+    let res = concert_program.send(
+        USER,
+        ConcertAction::CommitClaim {
+            commitment: commitment(&secret, USER.into()),
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::ClaimCommitted {
+            concert_id: CONCERT_ID,
+        }
+        .encode()
+    )));
+    let res = concert_program.send(USER, ConcertAction::ClaimTickets { secret });
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::TicketsClaimed {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+        }
+        .encode()
+    )));
+    // Debe fallar porque los boletos ya se reclamaron
+    let res = concert_program.send(OTHER_USER, ConcertAction::ClaimTickets { secret });
+    assert!(res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.user_tickets(USER.into()).len(), 1);
+}
+
+//Revisamos que los boletos regalados sean de quien los recibe y que quien pago quede registrado
+#[test]
+fn gift_tickets() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+
+    system.mint_to(OTHER_USER, 10_000);
+    let res = concert_program.send_with_value(
+        OTHER_USER,
+        ConcertAction::BuyTickets {
+            amount: AMOUNT,
+            metadata: vec![None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: Some(TicketRecipient::Account(USER.into())),
+        },
+        1_000,
+    );
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::Purchase {
+            concert_id: CONCERT_ID,
+            amount: AMOUNT,
+        }
+        .encode()
+    )));
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.ticket_payers.len(), 1);
+    assert_eq!(state.ticket_payers[0].1, OTHER_USER.into());
+    assert_eq!(state.escrow[0].0, OTHER_USER.into());
+    assert!(state.operator_sales.is_empty());
+    assert_eq!(state.user_tickets(USER.into()).len(), 1);
+}
+
+//Revisamos que el numero de boletos cambie sin quitar boletos vendidos y que quede el historial
+#[test]
+fn change_supply() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+
+    // Debe fallar porque solo el creador puede cambiar el numero de boletos
+    let res = concert_program.send(OTHER_USER, ConcertAction::IncreaseSupply { amount: 10 });
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::IncreaseSupply { amount: 10 });
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::SupplyChanged {
+            concert_id: CONCERT_ID,
+            previous_supply: NUMBER_OF_TICKETS,
+            new_supply: NUMBER_OF_TICKETS + 10,
+        }
+        .encode()
+    )));
+    // Debe fallar porque no se pueden quitar los boletos vendidos
+    let res = concert_program.send(
+        USER,
+        ConcertAction::DecreaseSupply {
+            amount: NUMBER_OF_TICKETS + 10,
+        },
+    );
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::DecreaseSupply { amount: 20 });
+    assert!(!res.main_failed());
+
+    check_current_concert(
+        &concert_program,
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        DATE,
+        NUMBER_OF_TICKETS - 10,
+        NUMBER_OF_TICKETS - 10 - AMOUNT,
+    );
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.supply_changes.len(), 2);
+}
+
+//Revisamos que al cambiar la fecha los compradores puedan pedir su reembolso y los demas conserven sus boletos
+#[test]
+fn reschedule_refund() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Create {
+            creator: USER.into(),
+            name: String::from("Stromae"),
+            description: String::from("Stromae en la CDMX 21/06/2023"),
+            number_of_tickets: NUMBER_OF_TICKETS,
+            date: DATE,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 1_000 },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
+        },
+    );
+    assert!(!res.main_failed());
+
+    system.mint_to(USER, 10_000);
+    system.mint_to(OTHER_USER, 10_000);
+    for buyer in [USER, OTHER_USER] {
+        let res = concert_program.send_with_value(
+            buyer,
+            ConcertAction::BuyTickets {
+                amount: AMOUNT,
+                metadata: vec![None],
+                request_id: None,
+                seats: Vec::new(),
+                promo_code: None,
+                recipient: None,
+            },
+            1_000,
+        );
+        assert!(!res.main_failed());
+    }
+    // Debe fallar porque la fecha no ha cambiado
+    let res = concert_program.send(OTHER_USER, ConcertAction::RequestRescheduleRefund);
+    assert!(res.main_failed());
+
+    let update = |date: Option<u128>| ConcertAction::UpdateConcert {
+        name: None,
+        description: Some(String::from("Stromae en la CDMX 28/06/2023")),
+        date,
+    };
+    // Debe fallar porque solo el creador puede cambiar el concierto
+    let res = concert_program.send(OTHER_USER, update(Some(280623)));
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, update(None));
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::ConcertUpdated {
+            concert_id: CONCERT_ID,
+        }
+        .encode()
+    )));
+    let res = concert_program.send(USER, update(Some(280623)));
+    assert!(!res.main_failed());
+    let state: State = concert_program.read_state().expect("Can't read state");
+    let refund_deadline = state
+        .reschedule_refund_deadline
+        .expect("The refund window should be open");
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::Rescheduled {
+            concert_id: CONCERT_ID,
+            previous_date: DATE,
+            new_date: 280623,
+            refund_deadline,
+        }
+        .encode()
+    )));
+
+    //Quien pide el reembolso aprueba al concierto para que recupere sus tokens
+    let mtk_program = system.get_program(MTK_ID);
+    let res = mtk_program.send(
+        OTHER_USER,
+        MyMTKAction::Approve {
+            account: CONCERT_PROGRAM_ID.into(),
+        },
+    );
+    assert!(!res.main_failed());
+    let res = concert_program.send(OTHER_USER, ConcertAction::RequestRescheduleRefund);
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::RescheduleRefund {
+            concert_id: CONCERT_ID,
+            amount: 1_000,
+            tickets: AMOUNT,
+        }
+        .encode()
+    )));
+    // Debe fallar porque ya no tiene nada que reembolsar
+    let res = concert_program.send(OTHER_USER, ConcertAction::RequestRescheduleRefund);
+    assert!(res.main_failed());
+
+    check_current_concert(
+        &concert_program,
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 28/06/2023"),
+        280623,
+        NUMBER_OF_TICKETS,
+        NUMBER_OF_TICKETS - AMOUNT,
+    );
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.proceeds, 1_000);
+    assert!(state.user_tickets(OTHER_USER.into()).is_empty());
+}
+
+//Revisamos que los avisos lleguen al buzon de los compradores y queden en el historial
+#[test]
+fn announcements() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+    let res = concert_program.send(
+        OTHER_USER,
+        ConcertAction::BuyTickets {
+            amount: AMOUNT,
+            metadata: vec![None],
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: None,
+        },
+    );
+    assert!(!res.main_failed());
+
+    let message = String::from("Las puertas abren a las 19:00");
+    // Debe fallar porque solo el creador puede mandar avisos
+    let res = concert_program.send(
+        OTHER_USER,
+        ConcertAction::Announce {
+            message: message.clone(),
+        },
+    );
+    assert!(res.main_failed());
+    let res = concert_program.send(
+        USER,
+        ConcertAction::Announce {
+            message: message.clone(),
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::AnnouncementDelivered {
+            concert_id: CONCERT_ID,
+            delivered: 2,
+            remaining: 0,
+        }
+        .encode()
+    )));
+    assert!(res.contains(&(
+        OTHER_USER,
+        ConcertEvent::Announcement {
+            concert_id: CONCERT_ID,
+            message: message.clone(),
+        }
+        .encode()
+    )));
+    // Debe fallar porque ya no hay aviso pendiente de entregar
+    let res = concert_program.send(OTHER_USER, ConcertAction::DeliverAnnouncement);
+    assert!(res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.announcements.len(), 1);
+    assert_eq!(state.announcements[0].message, message);
+    assert_eq!(state.announcements[0].delivered, 2);
+}
+
+//Revisamos que solo quien uso un boleto en la entrada reclame una insignia por concierto,
+//y que su token se cree en el programa de multitoken a nombre del concierto
+#[test]
+fn attendance_badges() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+    let ticket_id = CONCERT_ID + 2;
+
+    // Debe fallar porque solo el creador o un operador pueden marcar boletos en la entrada
+    let res = concert_program.send(OTHER_USER, ConcertAction::RedeemTicket { ticket_id });
+    assert!(res.main_failed());
+    // Debe fallar porque todavia no se usa ningun boleto en la entrada
+    let res = concert_program.send(USER, ConcertAction::ClaimBadge);
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::RedeemTicket { ticket_id });
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::TicketRedeemed {
+            concert_id: CONCERT_ID,
+            ticket_id,
+            holder: USER.into(),
+        }
+        .encode()
+    )));
+    // Debe fallar porque el boleto ya se uso
+    let res = concert_program.send(USER, ConcertAction::RedeemTicket { ticket_id });
+    assert!(res.main_failed());
+
+    // Debe fallar porque OTHER_USER no uso ningun boleto en la entrada
+    let res = concert_program.send(OTHER_USER, ConcertAction::ClaimBadge);
+    assert!(res.main_failed());
+    let res = concert_program.send(USER, ConcertAction::ClaimBadge);
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::BadgeClaimed {
+            concert_id: CONCERT_ID,
+            token_id: u64::MAX as u128,
+        }
+        .encode()
+    )));
+    // Debe fallar porque solo hay una insignia por concierto
+    let res = concert_program.send(USER, ConcertAction::ClaimBadge);
+    assert!(res.main_failed());
+
+    let state: State = concert_program.read_state().expect("Can't read state");
+    let badges = state.user_badges(USER.into());
+    assert_eq!(badges.len(), 1);
+    assert_eq!(badges[0].name, "Stromae");
+    assert_eq!(badges[0].date, DATE);
+
+    //El token de la insignia lo tiene el concierto y no quien la reclamo, asi no la puede transferir
+    let mtk_program = system.get_program(MTK_ID);
+    let res = mtk_program.send(
+        USER,
+        MyMTKAction::BalanceOfBatch {
+            accounts: vec![CONCERT_PROGRAM_ID.into(), USER.into()],
+            ids: vec![u64::MAX as u128, u64::MAX as u128],
+        },
+    );
+    assert!(res.contains(&(
+        USER,
+        MTKEvent::BalanceOf(vec![
+            BalanceReply {
+                account: CONCERT_PROGRAM_ID.into(),
+                id: u64::MAX as u128,
+                amount: 1,
+            },
+            BalanceReply {
+                account: USER.into(),
+                id: u64::MAX as u128,
+                amount: 0,
+            },
+        ])
+        .encode()
+    )));
+}
+
+//Revisamos que en la entrada no se puedan usar los boletos de un concierto anterior
+#[test]
+fn redeem_previous_concert_ticket() {
+    let system = init_system();
+    let concert_program = init_concert(&system);
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en la CDMX 21/06/2023"),
+        NUMBER_OF_TICKETS,
+        DATE,
+        CONCERT_ID,
+    );
+    buy(&concert_program, CONCERT_ID, AMOUNT, vec![None], false);
+    hold(&concert_program, CONCERT_ID);
+
+    //El id del segundo concierto sale del contador de boletos, que va en 1
+    let new_concert_id = CONCERT_ID + AMOUNT;
+    create(
+        &concert_program,
+        USER.into(),
+        String::from("Stromae"),
+        String::from("Stromae en Monterrey 28/06/2023"),
+        NUMBER_OF_TICKETS,
+        280623,
+        new_concert_id,
+    );
+    // Debe fallar porque el boleto es del concierto anterior aunque su id es mayor al del concierto
+    let old_ticket_id = CONCERT_ID + 2;
+    let res = concert_program.send(
+        USER,
+        ConcertAction::RedeemTicket {
+            ticket_id: old_ticket_id,
+        },
+    );
+    assert!(res.main_failed());
+
+    buy(&concert_program, new_concert_id, AMOUNT, vec![None], false);
+    let ticket_id = old_ticket_id + 1;
+    let state: State = concert_program.read_state().expect("Can't read state");
+    assert_eq!(state.first_ticket_id, ticket_id);
+    let res = concert_program.send(USER, ConcertAction::RedeemTicket { ticket_id });
+    assert!(res.contains(&(
+        USER,
+        ConcertEvent::TicketRedeemed {
+            concert_id: new_concert_id,
+            ticket_id,
+            holder: USER.into(),
+        }
+        .encode()
+    )));
+}
@@ -3,9 +3,21 @@ use venta_boletos_io::*;
 use gear_lib::multitoken::io::{InitConfig, TokenMetadata};
 use gstd::{prelude::*, ActorId, Encode};
 use gtest::{Program, System};
+use nft_io::InitNFT;
 
 pub const USER: u64 = 193;
+pub const OTHER_USER: u64 = 194;
+pub const THIRD_USER: u64 = 195;
+//El id que gtest le da al programa del concierto, es el primero que se crea
+pub const CONCERT_PROGRAM_ID: u64 = 1;
 pub const MTK_ID: u64 = 2;
+pub const NEW_MTK_ID: u64 = 3;
+//El programa de NFTs se crea en lugar del de multi-token, asi que recibe el mismo id
+pub const NFT_ID: u64 = 2;
+//Una cuenta que no es un programa, los mensajes que se le mandan nunca reciben respuesta
+pub const SILENT_TOKEN_CONTRACT: u64 = 300;
+//Los bloques que pasan hasta que se agota el tiempo de espera de la respuesta del programa de tokens
+pub const TOKEN_REPLY_BLOCKS: u32 = 21;
 pub const CONCERT_ID: u128 = 0;
 pub const NUMBER_OF_TICKETS: u128 = 100;
 pub const AMOUNT: u128 = 1;
@@ -41,6 +53,56 @@ pub fn init_concert(sys: &System) -> Program {
             InitConcert {
                 owner_id: USER.into(),
                 mtk_contract: MTK_ID.into(),
+                backend: TokenBackend::Multitoken,
+            },
+        )
+        .log()
+        .is_empty());
+
+    concert_program
+}
+
+//Emulamos el inicio de un concierto que crea los boletos en el smart contract de NFTs
+//Usamos el wasm de non-fungible-token que se obtiene al hacer make test
+pub fn init_nft_concert(sys: &System) -> Program {
+    let concert_program = Program::current(sys);
+    let nft_program = Program::from_file(sys, "target/nft.wasm");
+    let res = nft_program.send(
+        USER,
+        InitNFT {
+            name: String::from("NFTs for a concert"),
+            symbol: String::from("NFC"),
+            base_uri: String::from(""),
+            royalties: None,
+        },
+    );
+    assert!(!res.main_failed());
+    assert!(concert_program
+        .send(
+            USER,
+            InitConcert {
+                owner_id: USER.into(),
+                mtk_contract: NFT_ID.into(),
+                backend: TokenBackend::NonFungibleToken,
+            },
+        )
+        .log()
+        .is_empty());
+
+    concert_program
+}
+
+//Emulamos el inicio de un concierto que guarda los boletos en su registro interno
+//No necesita el smart contract de multi-token
+pub fn init_internal_concert(sys: &System) -> Program {
+    let concert_program = Program::current(sys);
+    assert!(concert_program
+        .send(
+            USER,
+            InitConcert {
+                owner_id: USER.into(),
+                mtk_contract: ActorId::zero(),
+                backend: TokenBackend::Internal,
             },
         )
         .log()
@@ -67,6 +129,11 @@ pub fn create(
             description,
             number_of_tickets,
             date,
+            seat_map: Vec::new(),
+            sale_mode: SaleMode::FixedPrice { price: 0 },
+            revenue_split: Vec::new(),
+            dispute_period: 0,
+            complimentary_tickets: 0,
         },
     );
 
@@ -90,7 +157,17 @@ pub fn buy(
     metadata: Vec<Option<TokenMetadata>>,
     should_fail: bool,
 ) {
-    let res = concert_program.send(USER, ConcertAction::BuyTickets { amount, metadata });
+    let res = concert_program.send(
+        USER,
+        ConcertAction::BuyTickets {
+            amount,
+            metadata,
+            request_id: None,
+            seats: Vec::new(),
+            promo_code: None,
+            recipient: None,
+        },
+    );
 
     if should_fail {
         assert!(res.main_failed());
@@ -99,6 +176,31 @@ pub fn buy(
     }
 }
 
+//Compramos un boleto para USER y le regalamos otro a OTHER_USER
+pub fn buy_and_gift(concert_program: &Program) {
+    for recipient in [None, Some(TicketRecipient::Account(OTHER_USER.into()))] {
+        let res = concert_program.send(
+            USER,
+            ConcertAction::BuyTickets {
+                amount: AMOUNT,
+                metadata: vec![None],
+                request_id: None,
+                seats: Vec::new(),
+                promo_code: None,
+                recipient,
+            },
+        );
+        assert!(res.contains(&(
+            USER,
+            ConcertEvent::Purchase {
+                concert_id: CONCERT_ID,
+                amount: AMOUNT,
+            }
+            .encode()
+        )));
+    }
+}
+
 //Volvemos el boleto en NFT
 pub fn hold(concert_program: &Program, concert_id: u128) {
     let res = concert_program.send(USER, ConcertAction::Hold {});
@@ -122,6 +224,7 @@ pub fn check_current_concert(
         date: true_date,
         number_of_tickets: true_number_of_tickets,
         tickets_left: true_tickets_left,
+        ..
     } = state.current_concert();
     if name != true_name {
         panic!("CONCERT: Concert name differs.");